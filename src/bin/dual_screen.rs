@@ -0,0 +1,65 @@
+use anyhow::Result;
+use epaper_ui::display::{open_displays, DisplayDriver, EpdConfig};
+use epaper_ui::fonts::FONT12;
+use epaper_ui::ui::{Label, Layout, Orientation, Screen, TextAlignment};
+use rppal::spi::SlaveSelect;
+
+// Wiring of the second panel on SPI0 CE1
+const RIGHT_RST_PIN: u8 = 5;
+const RIGHT_DC_PIN: u8 = 6;
+const RIGHT_BUSY_PIN: u8 = 13;
+
+fn main() -> Result<()> {
+    // Initialize logging
+    env_logger::init();
+    println!("Dual Screen Demo");
+
+    // Both panels are the same model, e.g. EPAPER_PANEL=epd4in2
+    let base = EpdConfig::from_env()?;
+    let right = base
+        .clone()
+        .slave_select(SlaveSelect::Ss1)
+        .rst_pin(RIGHT_RST_PIN)
+        .dc_pin(RIGHT_DC_PIN)
+        .busy_pin(RIGHT_BUSY_PIN);
+
+    // Open both panels, or simulated ones when EPAPER_SIM_DIR is set
+    let drivers = open_displays(&[("left", base), ("right", right)])?;
+
+    // Give every panel its own frame buffer and component tree
+    let titles = ["Left: Weather", "Right: Calendar"];
+    let mut screens: Vec<Screen<Box<dyn DisplayDriver + Send>>> = drivers
+        .into_iter()
+        .zip(titles)
+        .map(|(driver, title)| Screen::new(driver, Box::new(build_layout(title))))
+        .collect();
+
+    for screen in screens.iter_mut() {
+        screen.driver_mut().init()?;
+        screen.refresh()?;
+    }
+    println!("Both panels updated");
+
+    // Put the displays to sleep
+    for screen in screens.iter_mut() {
+        screen.driver_mut().sleep()?;
+    }
+
+    Ok(())
+}
+
+fn build_layout(title: &str) -> Layout {
+    let mut layout = Layout::new(0, 0, 0, 0, Orientation::Vertical);
+    layout.set_padding(20);
+    layout.set_spacing(15);
+
+    let mut header = Label::new(0, 0, 0, 40, title, &FONT12);
+    header.set_alignment(TextAlignment::Center);
+    layout.add_child(Box::new(header));
+
+    let mut body = Label::new(0, 0, 0, 30, "Each panel renders its own UI tree", &FONT12);
+    body.set_alignment(TextAlignment::Center);
+    layout.add_child(Box::new(body));
+
+    layout
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use epaper_ui::display::{Epd4in2, Paint, COLORED, UNCOLORED};
+use epaper_ui::display::{open_display, DisplayDriver, EpdConfig, Paint, COLORED, UNCOLORED};
 use epaper_ui::fonts::FONT12;
 use epaper_ui::ui::{Button, Component, Label, Layout, Orientation, TextAlignment};
 use std::thread;
@@ -10,8 +10,13 @@ fn main() -> Result<()> {
     env_logger::init();
     println!("Hello, E-Paper World!");
 
-    // Initialize the display
-    let mut epd = Epd4in2::new()?;
+    // Open the panel named in EPAPER_PANEL, or a simulated one when
+    // EPAPER_SIM_DIR is set (see display::env)
+    let mut epd = open_display(EpdConfig::from_env()?)?;
+    run(epd.as_mut())
+}
+
+fn run(epd: &mut dyn DisplayDriver) -> Result<()> {
     epd.init()?;
     
     // Create a frame buffer for the display
@@ -0,0 +1,220 @@
+use anyhow::{anyhow, Context, Result};
+use epaper_ui::display::driver::merge_gray_planes;
+use epaper_ui::display::epd4in2::{
+    DATA_START_TRANSMISSION_1, DATA_START_TRANSMISSION_2, DISPLAY_REFRESH, LUT_BLACK_TO_WHITE,
+    PARTIAL_IN, PARTIAL_OUT, PARTIAL_WINDOW, SSD_DISPLAY_UPDATE_CONTROL_2,
+    SSD_MASTER_ACTIVATION, SSD_SET_RAM_X_RANGE, SSD_SET_RAM_Y_RANGE, SSD_WRITE_RAM_BW,
+    SSD_WRITE_RAM_RED,
+};
+use epaper_ui::display::panel::Controller;
+use epaper_ui::display::Waveform;
+use epaper_ui::display::env::ENV_SIM_DIR;
+use epaper_ui::display::trace;
+use epaper_ui::display::{
+    find_panel, DisplayDriver, Epd4in2, EpdConfig, Panel, SimulatedDisplay, TraceReader,
+    TraceRecord,
+};
+use epaper_ui::ui::Rect;
+use std::env;
+
+// Sequence bit of SSD_DISPLAY_UPDATE_CONTROL_2 that drives the panel
+const SSD_SEQUENCE_DISPLAY: u8 = 0x04;
+
+fn main() -> Result<()> {
+    // Initialize logging
+    env_logger::init();
+
+    let path = env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow!("Usage: replay_trace <trace file>"))?;
+    let reader = TraceReader::open(&path).with_context(|| format!("Cannot open {}", path))?;
+    let panel = find_panel(reader.panel_name())
+        .ok_or_else(|| anyhow!("Unknown panel in trace: {}", reader.panel_name()))?;
+    println!("Replaying {} recorded on {}", path, panel.description);
+
+    // Replay on the panel, or decode the frames when EPAPER_SIM_DIR is set
+    let count = match env::var(ENV_SIM_DIR) {
+        Ok(dir) => {
+            let mut display = SimulatedDisplay::for_panel(panel, dir);
+            display.init()?;
+            let mut decoder = FrameDecoder::new(panel, display);
+            let mut count = 0;
+            for record in reader {
+                decoder.process(record?)?;
+                count += 1;
+            }
+            decoder.finish()?;
+            println!("Decoded {} frame(s)", decoder.frames);
+            count
+        }
+        Err(_) => {
+            // Configured like the other binaries, but on the panel of the trace
+            let mut epd = Epd4in2::new(EpdConfig::from_env()?.panel(panel))?;
+            trace::replay(&mut epd, reader)?
+        }
+    };
+    println!("Replayed {} record(s)", count);
+
+    Ok(())
+}
+
+// Rebuilds the frames sent in a trace from the controller commands, so a
+// trace can be inspected on the simulated backend
+struct FrameDecoder {
+    panel: &'static Panel,
+    display: SimulatedDisplay,
+    command: Option<u8>,
+    data: Vec<u8>,
+    // DTM1/DTM2 on UC81xx, BW/RED RAM on SSD16xx
+    first_plane: Option<Vec<u8>>,
+    second_plane: Option<Vec<u8>>,
+    partial: bool,
+    // The Gray4 LUTs are loaded (UC81xx)
+    gray: bool,
+    window: Rect,
+    sequence: u8,
+    frames: u32,
+}
+
+impl FrameDecoder {
+    fn new(panel: &'static Panel, display: SimulatedDisplay) -> Self {
+        Self {
+            panel,
+            display,
+            command: None,
+            data: Vec::new(),
+            first_plane: None,
+            second_plane: None,
+            partial: false,
+            gray: false,
+            window: Rect::new(0, 0, panel.width as i32, panel.height as i32),
+            sequence: 0,
+            frames: 0,
+        }
+    }
+
+    fn process(&mut self, record: TraceRecord) -> Result<()> {
+        match record {
+            TraceRecord::Command(command) => {
+                self.finish()?;
+                self.command = Some(command);
+                match (self.panel.controller, command) {
+                    (Controller::Uc81xx, DISPLAY_REFRESH) => self.show_uc81xx()?,
+                    (Controller::Uc81xx, PARTIAL_IN) => self.partial = true,
+                    (Controller::Uc81xx, PARTIAL_OUT) => self.partial = false,
+                    (Controller::Ssd16xx, SSD_MASTER_ACTIVATION) => self.show_ssd16xx()?,
+                    _ => {}
+                }
+            }
+            TraceRecord::Data(bytes) => self.data.extend_from_slice(&bytes),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Apply the data collected for the current command
+    fn finish(&mut self) -> Result<()> {
+        let data = std::mem::take(&mut self.data);
+        let Some(command) = self.command.take() else {
+            return Ok(());
+        };
+
+        match (self.panel.controller, command) {
+            (Controller::Uc81xx, DATA_START_TRANSMISSION_1) => self.first_plane = Some(data),
+            (Controller::Uc81xx, DATA_START_TRANSMISSION_2) => self.second_plane = Some(data),
+            (Controller::Uc81xx, LUT_BLACK_TO_WHITE) => {
+                // Temperature scaling keeps the level select byte of each phase
+                let levels = |table: &[u8]| table.iter().step_by(6).copied().collect::<Vec<u8>>();
+                self.gray = levels(&data) == levels(&Waveform::Gray4.lut().bw);
+            }
+            (Controller::Uc81xx, PARTIAL_WINDOW) if data.len() >= 8 => {
+                let value = |i: usize| (data[i] as i32) << 8 | data[i + 1] as i32;
+                let (x_start, x_end) = (value(0), value(2));
+                let (y_start, y_end) = (value(4), value(6));
+                self.window = Rect::new(x_start, y_start, x_end - x_start + 1, y_end - y_start + 1);
+            }
+            (Controller::Ssd16xx, SSD_WRITE_RAM_BW) => self.first_plane = Some(data),
+            (Controller::Ssd16xx, SSD_WRITE_RAM_RED) => self.second_plane = Some(data),
+            (Controller::Ssd16xx, SSD_SET_RAM_X_RANGE) if data.len() >= 2 => {
+                self.window.x = data[0] as i32 * 8;
+                self.window.width = (data[1] as i32 - data[0] as i32 + 1) * 8;
+            }
+            (Controller::Ssd16xx, SSD_SET_RAM_Y_RANGE) if data.len() >= 4 => {
+                let y_start = data[0] as i32 | (data[1] as i32) << 8;
+                let y_end = data[2] as i32 | (data[3] as i32) << 8;
+                self.window.y = y_start;
+                self.window.height = y_end - y_start + 1;
+            }
+            (Controller::Ssd16xx, SSD_DISPLAY_UPDATE_CONTROL_2) if !data.is_empty() => {
+                self.sequence = data[0];
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // DISPLAY_REFRESH shows whatever was transmitted since the last refresh
+    fn show_uc81xx(&mut self) -> Result<()> {
+        let first = self.first_plane.take();
+        let second = self.second_plane.take();
+        // The new image in DTM2, in the frame buffer polarity
+        let new_image = |data: &Vec<u8>| match self.panel.inverted_data {
+            true => data.iter().map(|byte| !byte).collect(),
+            false => data.clone(),
+        };
+
+        if self.partial {
+            if let Some(buffer) = second {
+                self.display.display_partial(&self.window, &new_image(&buffer))?;
+                self.frames += 1;
+            }
+            return Ok(());
+        }
+
+        match (first, second) {
+            (Some(black), Some(chromatic)) if self.panel.color.is_tricolor() => {
+                self.display.display_color_frame(&black, &chromatic)?
+            }
+            (Some(high), Some(low)) if self.gray => {
+                self.display.display_gray_frame(&merge_gray_planes(&high, &low))?
+            }
+            // DTM1 only holds the old image unless the frame was sent alone
+            (_, Some(frame)) => self.display.display_frame(&new_image(&frame))?,
+            (Some(frame), None) => self.display.display_frame(&frame)?,
+            (None, None) => {
+                log::warn!("Refresh without new frame data");
+                return Ok(());
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    // Master activation only shows a frame when the sequence drives the panel
+    fn show_ssd16xx(&mut self) -> Result<()> {
+        if self.sequence & SSD_SEQUENCE_DISPLAY == 0 {
+            return Ok(());
+        }
+        let Some(black) = self.first_plane.take() else {
+            log::warn!("Refresh without new frame data");
+            return Ok(());
+        };
+        let red = self.second_plane.take();
+
+        let full = self.window.x == 0
+            && self.window.y == 0
+            && self.window.width >= self.panel.width as i32
+            && self.window.height >= self.panel.height as i32;
+        match red {
+            Some(red) if full && self.panel.color.is_tricolor() => {
+                // The red RAM uses a set bit for a colored pixel
+                let chromatic: Vec<u8> = red.iter().map(|byte| !byte).collect();
+                self.display.display_color_frame(&black, &chromatic)?
+            }
+            _ if full => self.display.display_frame(&black)?,
+            _ => self.display.display_partial(&self.window, &black)?,
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
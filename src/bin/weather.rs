@@ -1,5 +1,6 @@
 use anyhow::Result;
-use epaper_ui::display::{Epd4in2, Paint, COLORED, UNCOLORED};
+use epaper_ui::display::driver::{byte_aligned, crop_window};
+use epaper_ui::display::{open_display, DisplayDriver, EpdConfig, Paint, COLORED, UNCOLORED};
 use epaper_ui::fonts::FONT12;
 use epaper_ui::ui::{Component, Label, Layout, Orientation, Rect, TextAlignment};
 use std::thread;
@@ -45,6 +46,9 @@ impl Component for DividerLine {
     }
 }
 
+// Wind speed above which the wind row is highlighted as a warning
+const WIND_WARNING_KMH: f32 = 40.0;
+
 // A simple weather data struct (in a real app, this would come from an API)
 struct WeatherData {
     location: String,
@@ -71,17 +75,35 @@ fn main() -> Result<()> {
     env_logger::init();
     println!("Weather Display Demo");
 
+    // Open the panel named in EPAPER_PANEL, or a simulated one when
+    // EPAPER_SIM_DIR is set (see display::env)
+    let mut epd = open_display(EpdConfig::from_env()?)?;
+    run(epd.as_mut())
+}
+
+// Send the paint buffer, including the color plane on tri-color panels
+fn show(epd: &mut dyn DisplayDriver, paint: &Paint) -> Result<()> {
+    match paint.get_color_image() {
+        Some(color) => epd.display_color_frame(paint.get_image(), color)?,
+        None => epd.display_frame(paint.get_image())?,
+    }
+    Ok(())
+}
+
+fn run(epd: &mut dyn DisplayDriver) -> Result<()> {
     // Get sample weather data
     let weather = WeatherData::sample_data();
 
-    // Initialize the display
-    let mut epd = Epd4in2::new()?;
     epd.init()?;
     
     // Create a frame buffer for the display
     let width = epd.width();
     let height = epd.height();
-    let mut paint = Paint::new(width, height);
+    let mut paint = if epd.capabilities().color {
+        Paint::new_tricolor(width, height)
+    } else {
+        Paint::new(width, height)
+    };
     
     // Clear the buffer to white
     paint.clear(UNCOLORED);
@@ -151,6 +173,7 @@ fn main() -> Result<()> {
         &FONT12
     );
     wind.set_alignment(TextAlignment::Left);
+    wind.set_highlighted(weather.wind_speed > WIND_WARNING_KMH);
     details_layout.add_child(Box::new(wind));
     
     // Add the details layout to the main layout
@@ -172,7 +195,7 @@ fn main() -> Result<()> {
     paint.draw_rectangle(2, 2, width as i32 - 3, height as i32 - 3, COLORED);
     
     // Display the frame buffer
-    epd.display_frame(paint.get_image())?;
+    show(epd, &paint)?;
     
     // Wait for a few seconds
     println!("Weather display updated. Sleeping for 10 seconds...");
@@ -215,8 +238,19 @@ fn main() -> Result<()> {
     // Draw the border again
     paint.draw_rectangle(2, 2, width as i32 - 3, height as i32 - 3, COLORED);
     
-    // Update the display
-    epd.display_frame(paint.get_image())?;
+    // Update only the changed rows when the panel supports partial refresh
+    if epd.capabilities().partial && !paint.is_tricolor() {
+        let changed = [
+            *main_layout.children()[2].bounds(),
+            *main_layout.children()[main_layout.children().len() - 1].bounds(),
+        ];
+        for region in changed.iter().map(byte_aligned) {
+            let window = crop_window(paint.get_image(), width, &region);
+            epd.display_partial(&region, &window)?;
+        }
+    } else {
+        show(epd, &paint)?;
+    }
     
     println!("Weather display updated with new data. Sleeping for 5 seconds...");
     thread::sleep(Duration::from_secs(5));
@@ -0,0 +1,290 @@
+use crate::display::driver::{check_buffer_size, DisplayDriver};
+use crate::display::epd4in2::{Epd4in2, SleepStep, WakeStep, BUSY_POLL_INTERVAL_MS, RESET_PULSE};
+use crate::display::error::{EpdError, Result};
+use crate::display::interface::EpdInterface;
+use crate::display::panel::InitStep;
+use crate::display::trace::TraceRecord;
+use crate::ui::Rect;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+/// Async front end for `Epd4in2`, available with the `async` feature
+///
+/// Commands and frame data are sent exactly as by the blocking driver, but
+/// delays and the wait for the BUSY line are tokio timers, so other tasks
+/// keep running during the 2-15 seconds of a refresh. SPI transfers are
+/// still blocking and take a few milliseconds per frame.
+///
+/// ```no_run
+/// # async fn example() -> epaper_ui::display::error::Result<()> {
+/// use epaper_ui::display::{AsyncEpd, Epd4in2, EpdConfig};
+///
+/// let mut epd = AsyncEpd::new(Epd4in2::new(EpdConfig::default())?);
+/// epd.init().await?;
+/// let frame = vec![0xFF; epd.frame_size()];
+/// epd.display_frame(&frame).await?;
+/// epd.sleep().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncEpd<I: EpdInterface> {
+    epd: Epd4in2<I>,
+}
+
+impl<I: EpdInterface> AsyncEpd<I> {
+    pub fn new(epd: Epd4in2<I>) -> Self {
+        Self { epd }
+    }
+
+    /// The wrapped blocking driver
+    pub fn inner(&self) -> &Epd4in2<I> {
+        &self.epd
+    }
+
+    /// Mutable access to the wrapped blocking driver
+    pub fn inner_mut(&mut self) -> &mut Epd4in2<I> {
+        &mut self.epd
+    }
+
+    /// Release the wrapped blocking driver
+    pub fn into_inner(self) -> Epd4in2<I> {
+        self.epd
+    }
+
+    pub fn width(&self) -> u32 {
+        self.epd.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.epd.height()
+    }
+
+    /// Size in bytes of a full frame buffer
+    pub fn frame_size(&self) -> usize {
+        self.epd.frame_size()
+    }
+
+    // Reset the display
+    pub async fn reset(&mut self) -> Result<()> {
+        for (high, ms) in RESET_PULSE {
+            self.epd.set_reset_line(high)?;
+            self.delay_ms(ms).await;
+        }
+        Ok(())
+    }
+
+    // Fixed delay between commands, recorded when tracing
+    async fn delay_ms(&mut self, ms: u64) {
+        self.epd.record(|| TraceRecord::Delay(ms));
+        time::sleep(Duration::from_millis(ms)).await;
+    }
+
+    // Wait until the busy pin is released without blocking the thread
+    pub async fn wait_until_idle(&mut self) -> Result<()> {
+        log::debug!("Waiting for display to be ready...");
+        self.epd.flush_trace();
+        let started = Instant::now();
+        while self.epd.is_busy()? {
+            let waited = started.elapsed();
+            if waited >= self.epd.busy_timeout() {
+                log::error!("Display still busy after {:?}", waited);
+                self.epd.record(|| TraceRecord::Wait(waited));
+                return Err(EpdError::BusyTimeout(waited));
+            }
+            time::sleep(Duration::from_millis(BUSY_POLL_INTERVAL_MS)).await;
+        }
+        let waited = started.elapsed();
+        self.epd.record(|| TraceRecord::Wait(waited));
+        log::debug!("Display is ready.");
+        Ok(())
+    }
+
+    /// Bring the panel out of reset and configure it for drawing
+    pub async fn init(&mut self) -> Result<()> {
+        self.epd.begin_init()?;
+        self.reset().await?;
+
+        for step in self.epd.panel().init_sequence {
+            match *step {
+                InitStep::Command(command, data) => self.epd.send_init_command(command, data)?,
+                InitStep::WaitIdle => self.wait_until_idle().await?,
+                InitStep::Delay(ms) => self.delay_ms(ms).await,
+            }
+        }
+
+        self.epd.finish_init()
+    }
+
+    // Make sure the panel can accept a frame, waking it if needed
+    async fn ensure_awake(&mut self, operation: &'static str) -> Result<()> {
+        match self.epd.wake_step(operation)? {
+            WakeStep::Ready => Ok(()),
+            WakeStep::PowerOn => {
+                self.epd.start_power_on()?;
+                self.wait_until_idle().await?;
+                self.epd.finish_power_on();
+                Ok(())
+            }
+            WakeStep::Init => self.init().await,
+        }
+    }
+
+    /// Transmit a full frame and await the end of the refresh
+    pub async fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+        check_buffer_size(frame_buffer, self.frame_size())?;
+        self.ensure_awake("display a frame").await?;
+
+        self.epd.start_frame(frame_buffer)?;
+        self.wait_until_idle().await
+    }
+
+    /// Transmit a window and await the end of its refresh, see
+    /// `DisplayDriver::display_partial`
+    pub async fn display_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        self.epd.check_partial(rect, buffer)?;
+        self.ensure_awake("display a partial frame").await?;
+
+        self.epd.start_partial(rect, buffer)?;
+        self.wait_until_idle().await?;
+        self.epd.finish_partial(rect, buffer)
+    }
+
+    /// Transmit a 4-level grayscale frame and await the end of the refresh
+    pub async fn display_gray_frame(&mut self, gray_buffer: &[u8]) -> Result<()> {
+        self.epd.check_gray_frame(gray_buffer)?;
+        self.ensure_awake("display a grayscale frame").await?;
+
+        self.epd.start_gray_frame(gray_buffer)?;
+        self.wait_until_idle().await
+    }
+
+    /// Transmit a black and a red/yellow frame and await the end of the refresh
+    pub async fn display_color_frame(&mut self, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        self.epd.check_color_frame(black, chromatic)?;
+        self.ensure_awake("display a color frame").await?;
+
+        self.epd.start_color_frame(black, chromatic)?;
+        self.wait_until_idle().await
+    }
+
+    /// Turn off the panel power while keeping the controller configured
+    pub async fn power_off(&mut self) -> Result<()> {
+        if self.epd.power_off_needed()? {
+            self.epd.start_power_off()?;
+            self.wait_until_idle().await?;
+            self.epd.finish_power_off();
+        }
+        Ok(())
+    }
+
+    /// Put the panel into its lowest power state
+    pub async fn sleep(&mut self) -> Result<()> {
+        match self.epd.sleep_step()? {
+            SleepStep::Asleep => return Ok(()),
+            SleepStep::PowerOff => self.power_off().await?,
+            SleepStep::DeepSleep => {}
+        }
+
+        self.epd.enter_deep_sleep()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::epd4in2::{
+        PowerState, DATA_START_TRANSMISSION_2, DEEP_SLEEP, DISPLAY_REFRESH, PARTIAL_IN, PARTIAL_OUT,
+        PARTIAL_WINDOW, POWER_OFF, POWER_ON,
+    };
+    use crate::display::mock::{MockEvent, MockInterface};
+    use crate::display::panel::EPD_7IN5_V2;
+
+    fn async_epd() -> AsyncEpd<MockInterface> {
+        AsyncEpd::new(Epd4in2::with_panel(MockInterface::new(), &EPD_7IN5_V2))
+    }
+
+    // Traffic on the bus and pins; delays are tokio timers in the async driver
+    fn traffic(mock: &MockInterface) -> Vec<MockEvent> {
+        let events = mock.events().iter().cloned();
+        events.filter(|event| !matches!(event, MockEvent::Delay(_))).collect()
+    }
+
+    // The traffic of the blocking driver for the same calls
+    fn blocking_traffic(f: impl FnOnce(&mut Epd4in2<MockInterface>)) -> Vec<MockEvent> {
+        let mut epd = Epd4in2::with_panel(MockInterface::new(), &EPD_7IN5_V2);
+        f(&mut epd);
+        traffic(epd.interface())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn init_sends_the_blocking_sequence() {
+        let mut epd = async_epd();
+        epd.init().await.unwrap();
+
+        let expected = blocking_traffic(|epd| epd.init().unwrap());
+        assert_eq!(traffic(epd.inner().interface()), expected);
+        assert_eq!(epd.inner().power_state(), PowerState::Awake);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn frame_and_sleep_send_the_blocking_sequence() {
+        let mut epd = async_epd();
+        let frame = vec![0x0F; epd.frame_size()];
+        epd.init().await.unwrap();
+        epd.display_frame(&frame).await.unwrap();
+        epd.sleep().await.unwrap();
+
+        let expected = blocking_traffic(|epd| {
+            epd.init().unwrap();
+            epd.display_frame(&frame).unwrap();
+            epd.sleep().unwrap();
+        });
+        assert_eq!(traffic(epd.inner().interface()), expected);
+        assert_eq!(epd.inner().power_state(), PowerState::DeepSleep);
+        let commands = epd.inner().interface().commands();
+        assert!(commands.ends_with(&[DATA_START_TRANSMISSION_2, DISPLAY_REFRESH, POWER_OFF, DEEP_SLEEP]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn partial_refresh_enters_and_leaves_partial_mode() {
+        let mut epd = async_epd();
+        epd.init().await.unwrap();
+        epd.power_off().await.unwrap();
+        epd.inner_mut().interface_mut().clear();
+
+        let rect = Rect::new(8, 4, 16, 2);
+        epd.display_partial(&rect, &[0x00; 4]).await.unwrap();
+        assert_eq!(
+            epd.inner().interface().commands(),
+            [POWER_ON, PARTIAL_IN, PARTIAL_WINDOW, DATA_START_TRANSMISSION_2, DISPLAY_REFRESH, PARTIAL_OUT]
+        );
+        assert_eq!(epd.inner().power_state(), PowerState::Awake);
+
+        assert!(matches!(
+            epd.display_partial(&Rect::new(4, 0, 8, 1), &[0x00]).await,
+            Err(EpdError::InvalidWindow { .. })
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn busy_wait_times_out_on_the_tokio_clock() {
+        let mut epd = async_epd();
+        epd.init().await.unwrap();
+        epd.inner_mut().set_busy_timeout(Duration::from_secs(1));
+        epd.inner_mut().interface_mut().hold_busy(1000);
+
+        let started = Instant::now();
+        let frame = vec![0xFF; epd.frame_size()];
+        assert!(matches!(epd.display_frame(&frame).await, Err(EpdError::BusyTimeout(_))));
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn frames_need_an_initialized_panel() {
+        let mut epd = async_epd();
+        let frame = vec![0xFF; epd.frame_size()];
+        assert!(matches!(epd.display_frame(&frame).await, Err(EpdError::InvalidState { .. })));
+        assert!(matches!(epd.sleep().await, Err(EpdError::InvalidState { .. })));
+        assert!(epd.inner().interface().events().is_empty());
+    }
+}
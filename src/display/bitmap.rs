@@ -0,0 +1,440 @@
+use crate::display::driver::{check_buffer_size, frame_stride};
+use crate::display::error::{EpdError, Result};
+use crate::display::image::{luma, Dither, GrayImage, ImageStyle};
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics::prelude::OriginDimensions;
+use std::fs;
+use std::path::Path;
+use tinybmp::Bmp;
+
+// Signature at the start of every PNG file
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+// Largest image decoded (16 megapixels, enough for camera photos); headers
+// can claim any size, so larger ones are refused before allocating
+const MAX_IMAGE_PIXELS: u64 = 1 << 24;
+
+/// How `Paint::draw_bitmap` treats the pixels of a bitmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitmapMode {
+    /// Set pixels are drawn, clear pixels leave the buffer untouched
+    Transparent,
+    /// Set pixels are drawn, clear pixels are drawn as paper
+    Opaque,
+    /// Set pixels are drawn as paper, clear pixels are drawn, e.g. for a
+    /// white icon on a black bar
+    Inverted,
+}
+
+/// A 1 bit per pixel image, e.g. an icon
+///
+/// Rows are padded to whole bytes with the most significant bit first, and
+/// a set bit marks an inked pixel. This is the usual layout of hand-encoded
+/// icons and of binary PBM files; any width is allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Bitmap {
+    /// Create a bitmap with no pixels set
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0x00; frame_stride(width) * height as usize],
+        }
+    }
+
+    /// Wrap existing 1 bit per pixel data
+    pub fn from_bytes(width: u32, height: u32, data: Vec<u8>) -> Result<Self> {
+        check_buffer_size(&data, frame_stride(width) * height as usize)?;
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Reduce a grayscale image to one bit per pixel with the given style
+    pub fn from_gray(image: &GrayImage, style: &ImageStyle) -> Self {
+        let mut bitmap = Bitmap::new(image.width(), image.height());
+        let width = image.width().max(1) as usize;
+        for (index, level) in image.quantize(2, style).into_iter().enumerate() {
+            bitmap.set((index % width) as u32, (index / width) as u32, level == 0);
+        }
+        bitmap
+    }
+
+    /// Decode a PBM/PGM/PPM, BMP or PNG image from memory
+    ///
+    /// Pixels darker than mid gray are set; use `GrayImage::decode` and
+    /// `from_gray` to dither photos instead.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let image = decode_gray(bytes)?;
+        Ok(Self::from_gray(&image, &ImageStyle::new().dither(Dither::Threshold(128))))
+    }
+
+    /// Load an image file, see `decode`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The packed rows
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Whether a pixel is set, `false` outside the bitmap
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let addr = y as usize * frame_stride(self.width) + x as usize / 8;
+        self.data[addr] & (0x80 >> (x % 8)) != 0
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, value: bool) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let addr = y as usize * frame_stride(self.width) + x as usize / 8;
+        if value {
+            self.data[addr] |= 0x80 >> (x % 8);
+        } else {
+            self.data[addr] &= !(0x80 >> (x % 8));
+        }
+    }
+}
+
+fn decode_error(reason: impl Into<String>) -> EpdError {
+    EpdError::ImageDecode(reason.into())
+}
+
+/// Decode any supported image format to grayscale, detected from its first bytes
+pub(crate) fn decode_gray(bytes: &[u8]) -> Result<GrayImage> {
+    match bytes {
+        [b'P', b'1'..=b'6', ..] => decode_netpbm(bytes),
+        [b'B', b'M', ..] => decode_bmp(bytes),
+        _ if bytes.starts_with(PNG_SIGNATURE) => decode_png(bytes),
+        _ => Err(decode_error("unknown image format")),
+    }
+}
+
+// Refuse images above MAX_IMAGE_PIXELS
+fn check_image_size(width: u32, height: u32) -> Result<()> {
+    if width as u64 * height as u64 > MAX_IMAGE_PIXELS {
+        return Err(decode_error(format!("image too large ({}x{})", width, height)));
+    }
+    Ok(())
+}
+
+// Blend a gray value with the given alpha over white paper
+fn over_white(value: u8, alpha: u8) -> u8 {
+    ((value as u32 * alpha as u32 + 255 * (255 - alpha as u32)) / 255) as u8
+}
+
+// Skip whitespace and comments in a Netpbm header or ASCII raster
+fn skip_separators(bytes: &[u8], mut pos: usize) -> usize {
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'#' => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() => pos += 1,
+            _ => break,
+        }
+    }
+    pos
+}
+
+// Read a decimal number, returning it and the position after it
+fn read_number(bytes: &[u8], pos: usize) -> Result<(u32, usize)> {
+    let start = skip_separators(bytes, pos);
+    let end = start + bytes[start..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let number = std::str::from_utf8(&bytes[start..end])
+        .ok()
+        .and_then(|digits| digits.parse().ok())
+        .ok_or_else(|| decode_error("bad Netpbm header"))?;
+    Ok((number, end))
+}
+
+// PBM (P1/P4), PGM (P2/P5) and PPM (P3/P6)
+fn decode_netpbm(bytes: &[u8]) -> Result<GrayImage> {
+    let kind = bytes[1];
+    let (width, pos) = read_number(bytes, 2)?;
+    let (height, mut pos) = read_number(bytes, pos)?;
+    check_image_size(width, height)?;
+    let max_value = match kind {
+        b'1' | b'4' => 1,
+        _ => {
+            let (max_value, end) = read_number(bytes, pos)?;
+            pos = end;
+            max_value
+        }
+    };
+    if max_value == 0 || max_value > u16::MAX as u32 {
+        return Err(decode_error("bad Netpbm maximum value"));
+    }
+
+    let channels = if matches!(kind, b'3' | b'6') { 3 } else { 1 };
+    let too_large = || decode_error("Netpbm image too large");
+    let count = (width as usize).checked_mul(height as usize).ok_or_else(too_large)?;
+    let sample_count = count.checked_mul(channels).ok_or_else(too_large)?;
+    // Samples take at least a byte (P4 packs 8, checked below), so the
+    // header cannot make us reserve more memory than the file could fill
+    if kind != b'4' && sample_count > bytes.len() - pos {
+        return Err(decode_error("truncated Netpbm data"));
+    }
+    let scale = |value: u32| (value.min(max_value) * 255 / max_value) as u8;
+
+    let samples: Vec<u32> = match kind {
+        // ASCII bitmap, digits may follow each other without separators
+        b'1' => {
+            let mut samples = Vec::with_capacity(count);
+            while samples.len() < count {
+                pos = skip_separators(bytes, pos);
+                match bytes.get(pos) {
+                    Some(b'0') => samples.push(1),
+                    Some(b'1') => samples.push(0),
+                    _ => return Err(decode_error("truncated PBM data")),
+                }
+                pos += 1;
+            }
+            samples
+        }
+        b'2' | b'3' => {
+            let mut samples = Vec::with_capacity(sample_count);
+            while samples.len() < sample_count {
+                let (value, end) = read_number(bytes, pos)?;
+                samples.push(value);
+                pos = end;
+            }
+            samples
+        }
+        // Binary bitmap, rows padded to whole bytes, a set bit is black
+        b'4' => {
+            let size = frame_stride(width)
+                .checked_mul(height as usize)
+                .ok_or_else(too_large)?;
+            let data = bytes.get(pos + 1..).unwrap_or_default();
+            let data = data
+                .get(..size)
+                .ok_or_else(|| decode_error("truncated PBM data"))?;
+            let bitmap = Bitmap::from_bytes(width, height, data.to_vec())?;
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| !bitmap.get(x, y) as u32)
+                .collect()
+        }
+        _ => {
+            let data = bytes.get(pos + 1..).unwrap_or_default();
+            let wide = max_value > 255;
+            let sample_size = if wide { 2 } else { 1 };
+            let size = sample_count.checked_mul(sample_size).ok_or_else(too_large)?;
+            let data = data
+                .get(..size)
+                .ok_or_else(|| decode_error("truncated Netpbm data"))?;
+            if wide {
+                data.chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as u32)
+                    .collect()
+            } else {
+                data.iter().map(|&value| value as u32).collect()
+            }
+        }
+    };
+
+    let pixels = if channels == 3 {
+        samples
+            .chunks_exact(3)
+            .map(|rgb| luma(scale(rgb[0]), scale(rgb[1]), scale(rgb[2])))
+            .collect()
+    } else {
+        samples.into_iter().map(scale).collect()
+    };
+    GrayImage::from_luma(width, height, pixels)
+}
+
+// Uncompressed and RLE BMP files in any bit depth tinybmp supports
+fn decode_bmp(bytes: &[u8]) -> Result<GrayImage> {
+    let bmp = Bmp::<Rgb888>::from_slice(bytes)
+        .map_err(|error| decode_error(format!("bad BMP file: {:?}", error)))?;
+    let size = bmp.size();
+    check_image_size(size.width, size.height)?;
+
+    let mut image = GrayImage::new(size.width, size.height);
+    for pixel in bmp.pixels() {
+        let (point, color) = (pixel.0, pixel.1);
+        image.set(point.x as u32, point.y as u32, luma(color.r(), color.g(), color.b()));
+    }
+    Ok(image)
+}
+
+// PNG in any color type, transparent pixels are drawn on white
+fn decode_png(bytes: &[u8]) -> Result<GrayImage> {
+    let png_error = |error: png::DecodingError| decode_error(format!("bad PNG file: {}", error));
+
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(png_error)?;
+    check_image_size(reader.info().width, reader.info().height)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(png_error)?;
+
+    let channels = info.color_type.samples();
+    let width = info.width as usize;
+    let mut pixels = Vec::with_capacity(width * info.height as usize);
+    for row in buffer[..info.buffer_size()].chunks(info.line_size) {
+        for pixel in row[..width * channels].chunks_exact(channels) {
+            pixels.push(match info.color_type {
+                png::ColorType::GrayscaleAlpha => over_white(pixel[0], pixel[1]),
+                png::ColorType::Rgb => luma(pixel[0], pixel[1], pixel[2]),
+                png::ColorType::Rgba => over_white(luma(pixel[0], pixel[1], pixel[2]), pixel[3]),
+                _ => pixel[0],
+            });
+        }
+    }
+    GrayImage::from_luma(info.width, info.height, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_decode_error<T>(result: Result<T>) -> bool {
+        matches!(result, Err(EpdError::ImageDecode(_)))
+    }
+
+    #[test]
+    fn netpbm_header_larger_than_the_data_is_rejected() {
+        assert!(is_decode_error(GrayImage::decode(b"P2\n100000 100000\n255\n0 0 0\n")));
+        assert!(is_decode_error(GrayImage::decode(b"P1\n100000 100000\n0 1 0\n")));
+        assert!(is_decode_error(GrayImage::decode(b"P6\n100000 100000\n255\n\x00\x00\x00")));
+        assert!(is_decode_error(GrayImage::decode(b"P4\n100000 100000\n\x00")));
+    }
+
+    fn is_too_large<T>(result: Result<T>) -> bool {
+        matches!(result, Err(EpdError::ImageDecode(reason)) if reason.starts_with("image too large"))
+    }
+
+    #[test]
+    fn png_larger_than_the_cap_is_rejected_before_decoding() {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 60000, 60000);
+        encoder.set_color(png::ColorType::Grayscale);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_chunk(png::chunk::IDAT, &[0x78, 0x9C]).unwrap();
+        drop(writer);
+        assert!(is_too_large(GrayImage::decode(&bytes)));
+    }
+
+    #[test]
+    fn bmp_larger_than_the_cap_is_rejected_before_decoding() {
+        // RLE8 with a two color palette and only an end of bitmap marker
+        let mut bytes = b"BM".to_vec();
+        for value in [64u32, 0, 62, 40, 60000, 60000] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend([1, 0, 8, 0]);
+        for value in [1u32, 2, 0, 0, 2, 0] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend([0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0]);
+        bytes.extend([0x00, 0x01]);
+        assert!(is_too_large(GrayImage::decode(&bytes)));
+    }
+
+    #[test]
+    fn netpbm_size_overflow_is_rejected() {
+        let header = format!("P6\n{} {}\n65535\n", u32::MAX, u32::MAX);
+        assert!(is_decode_error(GrayImage::decode(header.as_bytes())));
+    }
+
+    fn encode_png(width: u32, height: u32, color_type: png::ColorType, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn ascii_pbm_digits_may_run_together() {
+        let image = GrayImage::decode(b"P1\n# icon\n3 2\n010\n1 0 1\n").unwrap();
+        assert_eq!(image.pixels(), [255, 0, 255, 0, 255, 0]);
+    }
+
+    #[test]
+    fn binary_pbm_skips_the_row_padding() {
+        let image = GrayImage::decode(b"P4\n10 2\n\x80\x40\x00\x3F").unwrap();
+        let mut expected = [255; 20];
+        expected[0] = 0;
+        expected[9] = 0;
+        assert_eq!(image.pixels(), expected);
+
+        let bitmap = Bitmap::decode(b"P4\n10 2\n\x80\x40\x00\x3F").unwrap();
+        assert!(bitmap.get(0, 0) && bitmap.get(9, 0) && !bitmap.get(1, 0));
+        assert!(!bitmap.get(0, 1));
+    }
+
+    #[test]
+    fn pgm_values_are_scaled_to_8_bits() {
+        let ascii = GrayImage::decode(b"P2\n3 1\n15\n0 15 5\n").unwrap();
+        assert_eq!(ascii.pixels(), [0, 255, 85]);
+
+        let binary = GrayImage::decode(b"P5\n3 1\n255\n\x00\x80\xFF").unwrap();
+        assert_eq!(binary.pixels(), [0, 128, 255]);
+
+        let wide = GrayImage::decode(b"P5\n2 1\n65535\n\xFF\xFF\x80\x00").unwrap();
+        assert_eq!(wide.pixels(), [255, 127]);
+    }
+
+    #[test]
+    fn ppm_colors_are_converted_to_luma() {
+        let ascii = GrayImage::decode(b"P3\n2 1\n255\n255 0 0  255 255 255\n").unwrap();
+        assert_eq!(ascii.pixels(), [luma(255, 0, 0), 255]);
+
+        let binary = GrayImage::decode(b"P6\n2 1\n255\n\x00\xFF\x00\x00\x00\xFF").unwrap();
+        assert_eq!(binary.pixels(), [luma(0, 255, 0), luma(0, 0, 255)]);
+    }
+
+    #[test]
+    fn png_color_types_are_converted_to_gray() {
+        let gray = encode_png(2, 1, png::ColorType::Grayscale, &[0, 200]);
+        assert_eq!(GrayImage::decode(&gray).unwrap().pixels(), [0, 200]);
+
+        let rgb = encode_png(1, 1, png::ColorType::Rgb, &[255, 0, 0]);
+        assert_eq!(GrayImage::decode(&rgb).unwrap().pixels(), [luma(255, 0, 0)]);
+
+        // Transparent black is drawn as paper, opaque black stays black
+        let rgba = encode_png(2, 1, png::ColorType::Rgba, &[0, 0, 0, 0, 0, 0, 0, 255]);
+        assert_eq!(GrayImage::decode(&rgba).unwrap().pixels(), [255, 0]);
+
+        let gray_alpha = encode_png(1, 1, png::ColorType::GrayscaleAlpha, &[0, 128]);
+        assert_eq!(GrayImage::decode(&gray_alpha).unwrap().pixels(), [over_white(0, 128)]);
+    }
+
+    #[test]
+    fn corrupt_png_is_rejected() {
+        let mut bytes = encode_png(2, 2, png::ColorType::Grayscale, &[0, 1, 2, 3]);
+        bytes.truncate(PNG_SIGNATURE.len() + 10);
+        assert!(is_decode_error(GrayImage::decode(&bytes)));
+        assert!(is_decode_error(GrayImage::decode(b"GIF89a")));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::display::error::{EpdError, Result};
+use crate::display::panel::{find_panel, Panel, DEFAULT_PANEL};
+#[cfg(feature = "rppal")]
+use rppal::spi::{Bus, SlaveSelect};
+use std::env;
+use std::time::Duration;
+
+/// Panel model to drive, e.g. `EPAPER_PANEL=epd4in2`
+pub const ENV_PANEL: &str = "EPAPER_PANEL";
+
+// Pin definitions from epdif.h
+pub const DEFAULT_RST_PIN: u8 = 17;  // GPIO 17
+pub const DEFAULT_DC_PIN: u8 = 25;   // GPIO 25
+pub const DEFAULT_BUSY_PIN: u8 = 24; // GPIO 24
+
+// SPI settings of the Waveshare HAT (CS on GPIO 8 / CE0)
+pub const DEFAULT_SPI_SPEED_HZ: u32 = 10_000_000;
+pub const DEFAULT_CHUNK_SIZE: usize = 4000;
+
+// A full refresh takes up to ~15s at low temperatures
+pub const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// GPIOs taken by the SPI0 peripheral
+#[cfg(feature = "rppal")]
+const SPI0_PINS: [(u8, &str); 5] = [
+    (7, "SPI0 CE1"),
+    (8, "SPI0 CE0"),
+    (9, "SPI0 MISO"),
+    (10, "SPI0 MOSI"),
+    (11, "SPI0 SCLK"),
+];
+
+/// Wiring and SPI settings used to open a panel
+///
+/// The defaults match the Waveshare HAT. Use the builder methods to
+/// override individual settings:
+///
+/// ```no_run
+/// # #[cfg(feature = "rppal")] {
+/// use epaper_ui::display::{EpdConfig, Epd4in2};
+///
+/// let config = EpdConfig::default().rst_pin(5).spi_speed_hz(4_000_000);
+/// let epd = Epd4in2::new(config);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EpdConfig {
+    /// Panel model connected to the HAT
+    pub panel: &'static Panel,
+    /// GPIO driving the reset line
+    pub rst_pin: u8,
+    /// GPIO driving the data/command line
+    pub dc_pin: u8,
+    /// GPIO reading the busy line
+    pub busy_pin: u8,
+    /// SPI bus the panel is connected to
+    #[cfg(feature = "rppal")]
+    pub spi_bus: Bus,
+    /// Hardware chip select line (CE0 or CE1 on SPI0)
+    #[cfg(feature = "rppal")]
+    pub slave_select: SlaveSelect,
+    /// SPI clock speed in Hz
+    pub spi_speed_hz: u32,
+    /// Maximum number of bytes sent in a single SPI transfer
+    pub chunk_size: usize,
+    /// How long to wait for the BUSY line before giving up
+    pub busy_timeout: Duration,
+    /// MISO is connected to the controller's data line, so registers and
+    /// the temperature sensor can be read back
+    pub miso_wired: bool,
+}
+
+impl Default for EpdConfig {
+    fn default() -> Self {
+        Self {
+            panel: DEFAULT_PANEL,
+            rst_pin: DEFAULT_RST_PIN,
+            dc_pin: DEFAULT_DC_PIN,
+            busy_pin: DEFAULT_BUSY_PIN,
+            #[cfg(feature = "rppal")]
+            spi_bus: Bus::Spi0,
+            #[cfg(feature = "rppal")]
+            slave_select: SlaveSelect::Ss0,
+            spi_speed_hz: DEFAULT_SPI_SPEED_HZ,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            miso_wired: false,
+        }
+    }
+}
+
+impl EpdConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The default wiring with the panel model named in `EPAPER_PANEL`
+    pub fn from_env() -> Result<Self> {
+        match env::var(ENV_PANEL) {
+            Ok(name) => match find_panel(&name) {
+                Some(panel) => Ok(Self::default().panel(panel)),
+                None => Err(EpdError::UnknownPanel(name)),
+            },
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Select the panel model, e.g. one returned by `panel::find_panel`
+    pub fn panel(mut self, panel: &'static Panel) -> Self {
+        self.panel = panel;
+        self
+    }
+
+    pub fn rst_pin(mut self, pin: u8) -> Self {
+        self.rst_pin = pin;
+        self
+    }
+
+    pub fn dc_pin(mut self, pin: u8) -> Self {
+        self.dc_pin = pin;
+        self
+    }
+
+    pub fn busy_pin(mut self, pin: u8) -> Self {
+        self.busy_pin = pin;
+        self
+    }
+
+    #[cfg(feature = "rppal")]
+    pub fn spi_bus(mut self, bus: Bus) -> Self {
+        self.spi_bus = bus;
+        self
+    }
+
+    #[cfg(feature = "rppal")]
+    pub fn slave_select(mut self, slave_select: SlaveSelect) -> Self {
+        self.slave_select = slave_select;
+        self
+    }
+
+    /// Lower this for long cables if the panel shows corrupted frames
+    pub fn spi_speed_hz(mut self, speed_hz: u32) -> Self {
+        self.spi_speed_hz = speed_hz;
+        self
+    }
+
+    /// Must not exceed the spidev buffer size (4096 bytes by default)
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// A loose ribbon cable leaves BUSY low forever; this bounds the wait
+    pub fn busy_timeout(mut self, timeout: Duration) -> Self {
+        self.busy_timeout = timeout;
+        self
+    }
+
+    /// The Waveshare HAT leaves MISO unconnected; only enable this for
+    /// wiring that joins MISO to the panel's DIN line
+    pub fn miso_wired(mut self, wired: bool) -> Self {
+        self.miso_wired = wired;
+        self
+    }
+
+    /// Check that panels driven together do not share a GPIO or chip select
+    ///
+    /// Panels on one bus share the clock and data lines, but each needs its
+    /// own chip select and reset, DC and BUSY pins.
+    pub fn check_conflicts(configs: &[EpdConfig]) -> Result<()> {
+        let mut used: Vec<(u8, String)> = Vec::new();
+        #[cfg(feature = "rppal")]
+        if configs.iter().any(|config| config.spi_bus == Bus::Spi0) {
+            used.extend(SPI0_PINS.iter().map(|(pin, name)| (*pin, name.to_string())));
+        }
+
+        for (index, config) in configs.iter().enumerate() {
+            #[cfg(feature = "rppal")]
+            if let Some(other) = configs[..index].iter().position(|other| {
+                other.spi_bus == config.spi_bus && other.slave_select == config.slave_select
+            }) {
+                return Err(EpdError::ConfigConflict(format!(
+                    "panels {} and {} both use {:?} {:?}",
+                    other, index, config.spi_bus, config.slave_select
+                )));
+            }
+
+            for (pin, line) in [
+                (config.rst_pin, "RST"),
+                (config.dc_pin, "DC"),
+                (config.busy_pin, "BUSY"),
+            ] {
+                let usage = format!("{} of panel {}", line, index);
+                if let Some((_, owner)) = used.iter().find(|(used_pin, _)| *used_pin == pin) {
+                    return Err(EpdError::ConfigConflict(format!(
+                        "GPIO {} is used as {} and as {}",
+                        pin, owner, usage
+                    )));
+                }
+                used.push((pin, usage));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_conflict(result: Result<()>) -> bool {
+        matches!(result, Err(EpdError::ConfigConflict(_)))
+    }
+
+    // Second panel on its own pins (and CE1 when driven through rppal)
+    fn second_panel() -> EpdConfig {
+        let config = EpdConfig::default().rst_pin(5).dc_pin(6).busy_pin(13);
+        #[cfg(feature = "rppal")]
+        let config = config.slave_select(SlaveSelect::Ss1);
+        config
+    }
+
+    #[test]
+    fn panels_on_separate_pins_do_not_conflict() {
+        assert!(EpdConfig::check_conflicts(&[EpdConfig::default(), second_panel()]).is_ok());
+    }
+
+    #[test]
+    fn shared_gpio_is_a_conflict() {
+        let shared_busy = second_panel().busy_pin(DEFAULT_BUSY_PIN);
+        assert!(is_conflict(EpdConfig::check_conflicts(&[EpdConfig::default(), shared_busy])));
+
+        let same_pin_twice = EpdConfig::default().dc_pin(DEFAULT_RST_PIN);
+        assert!(is_conflict(EpdConfig::check_conflicts(&[same_pin_twice])));
+    }
+
+    #[cfg(feature = "rppal")]
+    #[test]
+    fn shared_chip_select_is_a_conflict() {
+        let same_select = second_panel().slave_select(SlaveSelect::Ss0);
+        assert!(is_conflict(EpdConfig::check_conflicts(&[EpdConfig::default(), same_select])));
+    }
+
+    #[cfg(feature = "rppal")]
+    #[test]
+    fn spi0_pins_are_reserved() {
+        let on_sclk = EpdConfig::default().rst_pin(11);
+        assert!(is_conflict(EpdConfig::check_conflicts(&[on_sclk])));
+    }
+}
@@ -0,0 +1,344 @@
+use crate::ui::Rect;
+use crate::display::error::{EpdError, Result};
+
+/// Refresh modes supported by a display driver
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RefreshCapabilities {
+    /// The panel can update a window without a full-screen refresh
+    pub partial: bool,
+    /// The panel has a fast (lower quality) waveform
+    pub fast: bool,
+    /// The panel can show four gray levels
+    pub grayscale: bool,
+    /// The panel has a third (red or yellow) color
+    pub color: bool,
+}
+
+/// Hardware-independent interface to an e-paper panel
+///
+/// Frame buffers use the layout produced by `Paint::get_image()`:
+/// one bit per pixel, rows packed MSB first, a set bit meaning white.
+pub trait DisplayDriver {
+    /// Width of the panel in pixels
+    fn width(&self) -> u32;
+
+    /// Height of the panel in pixels
+    fn height(&self) -> u32;
+
+    /// Bring the panel out of reset and configure it for drawing
+    fn init(&mut self) -> Result<()>;
+
+    /// Transmit a full frame and refresh the panel
+    fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()>;
+
+    /// Transmit the contents of a window and refresh only that area
+    ///
+    /// `rect` is in panel coordinates and must be byte aligned horizontally
+    /// (see `byte_aligned`). `buffer` holds only the window, in the same
+    /// layout as a full frame buffer.
+    fn display_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        let _ = (rect, buffer);
+        Err(EpdError::Unsupported("partial refresh"))
+    }
+
+    /// Transmit a 4-level grayscale frame and refresh the panel
+    ///
+    /// The buffer uses the 2 bits per pixel layout of `Paint::new_gray4`.
+    fn display_gray_frame(&mut self, gray_buffer: &[u8]) -> Result<()> {
+        let _ = gray_buffer;
+        Err(EpdError::Unsupported("grayscale"))
+    }
+
+    /// Transmit a black/white frame and a red/yellow frame, then refresh
+    ///
+    /// Both buffers use the 1-bit layout of `Paint::new_tricolor`, a cleared
+    /// bit marking a black or colored pixel respectively.
+    fn display_color_frame(&mut self, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        let _ = (black, chromatic);
+        Err(EpdError::Unsupported("color frames"))
+    }
+
+    /// Put the panel into its lowest power state
+    fn sleep(&mut self) -> Result<()>;
+
+    /// Describe the refresh modes this panel supports
+    fn capabilities(&self) -> RefreshCapabilities {
+        RefreshCapabilities::default()
+    }
+
+    /// Size in bytes of a full frame buffer
+    fn frame_size(&self) -> usize {
+        frame_stride(self.width()) * self.height() as usize
+    }
+}
+
+impl<D: DisplayDriver + ?Sized> DisplayDriver for Box<D> {
+    fn width(&self) -> u32 {
+        (**self).width()
+    }
+
+    fn height(&self) -> u32 {
+        (**self).height()
+    }
+
+    fn init(&mut self) -> Result<()> {
+        (**self).init()
+    }
+
+    fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+        (**self).display_frame(frame_buffer)
+    }
+
+    fn display_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        (**self).display_partial(rect, buffer)
+    }
+
+    fn display_gray_frame(&mut self, gray_buffer: &[u8]) -> Result<()> {
+        (**self).display_gray_frame(gray_buffer)
+    }
+
+    fn display_color_frame(&mut self, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        (**self).display_color_frame(black, chromatic)
+    }
+
+    fn sleep(&mut self) -> Result<()> {
+        (**self).sleep()
+    }
+
+    fn capabilities(&self) -> RefreshCapabilities {
+        (**self).capabilities()
+    }
+}
+
+/// Bytes per row of a frame buffer; rows are padded to a whole byte
+pub fn frame_stride(width: u32) -> usize {
+    width.div_ceil(8) as usize
+}
+
+/// Grow a rectangle horizontally so it starts and ends on a byte boundary
+pub fn byte_aligned(rect: &Rect) -> Rect {
+    let x0 = rect.x & !7;
+    let x1 = (rect.x + rect.width + 7) & !7;
+    Rect::new(x0, rect.y, x1 - x0, rect.height)
+}
+
+/// Check that a partial refresh window is usable on a panel of the given size
+pub fn validate_window(rect: &Rect, width: u32, height: u32) -> Result<()> {
+    if rect.x % 8 != 0 || rect.width % 8 != 0 {
+        return Err(EpdError::InvalidWindow {
+            rect: *rect,
+            reason: "not byte aligned",
+        });
+    }
+    if rect.width <= 0
+        || rect.height <= 0
+        || rect.x < 0
+        || rect.y < 0
+        || rect.x + rect.width > frame_stride(width) as i32 * 8
+        || rect.y + rect.height > height as i32
+    {
+        return Err(EpdError::InvalidWindow {
+            rect: *rect,
+            reason: "outside the panel",
+        });
+    }
+    Ok(())
+}
+
+/// Check that a buffer has the size expected by the panel or window
+pub fn check_buffer_size(buffer: &[u8], expected: usize) -> Result<()> {
+    if buffer.len() != expected {
+        return Err(EpdError::FrameSizeMismatch {
+            expected,
+            actual: buffer.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Size in bytes of the buffer for a byte-aligned window
+pub fn window_size(rect: &Rect) -> usize {
+    (rect.width / 8 * rect.height) as usize
+}
+
+/// Copy a byte-aligned window out of a full frame buffer
+pub fn crop_window(frame_buffer: &[u8], frame_width: u32, rect: &Rect) -> Vec<u8> {
+    let stride = frame_stride(frame_width);
+    let row_bytes = (rect.width / 8) as usize;
+    let mut window = Vec::with_capacity(window_size(rect));
+
+    for y in rect.y..rect.y + rect.height {
+        let start = y as usize * stride + (rect.x / 8) as usize;
+        window.extend_from_slice(&frame_buffer[start..start + row_bytes]);
+    }
+
+    window
+}
+
+/// Copy a byte-aligned window into a full frame buffer
+pub fn paste_window(frame_buffer: &mut [u8], frame_width: u32, rect: &Rect, window: &[u8]) {
+    let stride = frame_stride(frame_width);
+    let row_bytes = (rect.width / 8) as usize;
+
+    for (row, chunk) in window.chunks(row_bytes).enumerate() {
+        let start = (rect.y as usize + row) * stride + (rect.x / 8) as usize;
+        frame_buffer[start..start + row_bytes].copy_from_slice(chunk);
+    }
+}
+
+/// Bounding box of the pixels that differ between two frame buffers
+///
+/// Returns `None` when the frames are identical. The box is exact to the
+/// pixel; use `byte_aligned` before passing it to `display_partial`.
+pub fn changed_region(old: &[u8], new: &[u8], frame_width: u32) -> Option<Rect> {
+    let stride = frame_stride(frame_width);
+    let mut x0 = u32::MAX;
+    let mut x1 = 0;
+    let mut y0 = u32::MAX;
+    let mut y1 = 0;
+
+    for (y, (old_row, new_row)) in old.chunks(stride).zip(new.chunks(stride)).enumerate() {
+        for (byte, (a, b)) in old_row.iter().zip(new_row).enumerate() {
+            let diff = a ^ b;
+            if diff == 0 {
+                continue;
+            }
+            let first = byte as u32 * 8 + diff.leading_zeros();
+            let last = byte as u32 * 8 + 7 - diff.trailing_zeros();
+            if first >= frame_width {
+                // Only the padding bits at the end of the row differ
+                continue;
+            }
+            x0 = x0.min(first);
+            x1 = x1.max(last.min(frame_width - 1));
+            y0 = y0.min(y as u32);
+            y1 = y1.max(y as u32);
+        }
+    }
+
+    if y0 == u32::MAX {
+        return None;
+    }
+    Some(Rect::new(
+        x0 as i32,
+        y0 as i32,
+        (x1 - x0 + 1) as i32,
+        (y1 - y0 + 1) as i32,
+    ))
+}
+
+/// Split a 2 bits per pixel grayscale buffer into its high and low bit planes
+///
+/// Each plane has the 1 bit per pixel layout of a frame buffer. The
+/// controller takes the high bits as the "old" data and the low bits as the
+/// "new" data, which the grayscale waveform maps to four levels.
+pub fn split_gray_planes(gray_buffer: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut high = Vec::with_capacity(gray_buffer.len() / 2);
+    let mut low = Vec::with_capacity(gray_buffer.len() / 2);
+
+    for pair in gray_buffer.chunks(2) {
+        let mut high_byte = 0u8;
+        let mut low_byte = 0u8;
+        for (i, byte) in pair.iter().enumerate() {
+            for pixel in 0..4 {
+                let level = (byte >> (6 - 2 * pixel)) & 0x03;
+                let bit = 0x80 >> (i * 4 + pixel);
+                if level & 0x02 != 0 {
+                    high_byte |= bit;
+                }
+                if level & 0x01 != 0 {
+                    low_byte |= bit;
+                }
+            }
+        }
+        high.push(high_byte);
+        low.push(low_byte);
+    }
+
+    (high, low)
+}
+
+/// Combine the planes produced by `split_gray_planes` into a 2 bits per
+/// pixel grayscale buffer again
+pub fn merge_gray_planes(high: &[u8], low: &[u8]) -> Vec<u8> {
+    let mut gray_buffer = Vec::with_capacity(high.len() * 2);
+
+    for (high_byte, low_byte) in high.iter().zip(low) {
+        for half in 0..2 {
+            let mut byte = 0u8;
+            for pixel in 0..4 {
+                let bit = 0x80 >> (half * 4 + pixel);
+                let level = (((high_byte & bit) != 0) as u8) << 1 | ((low_byte & bit) != 0) as u8;
+                byte |= level << (6 - 2 * pixel);
+            }
+            gray_buffer.push(byte);
+        }
+    }
+
+    gray_buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_region_is_exact_to_the_pixel() {
+        let old = vec![0xFF; 4 * 3];
+        let mut new = old.clone();
+        assert_eq!(changed_region(&old, &new, 32), None);
+
+        new[4 + 1] = 0b1110_1111; // x = 11, y = 1
+        new[2 * 4 + 2] = 0b1111_1101; // x = 22, y = 2
+        assert_eq!(changed_region(&old, &new, 32), Some(Rect::new(11, 1, 12, 2)));
+    }
+
+    #[test]
+    fn changed_region_ignores_row_padding() {
+        // 12 pixels wide, the low nibble of every second byte is padding
+        let old = vec![0xFF; 2 * 2];
+        let mut new = old.clone();
+        new[3] = 0xF0;
+        assert_eq!(changed_region(&old, &new, 12), None);
+
+        new[3] = 0xE0;
+        assert_eq!(changed_region(&old, &new, 12), Some(Rect::new(11, 1, 1, 1)));
+    }
+
+    #[test]
+    fn byte_aligned_grows_to_whole_bytes() {
+        assert_eq!(byte_aligned(&Rect::new(11, 1, 12, 2)), Rect::new(8, 1, 16, 2));
+        assert_eq!(byte_aligned(&Rect::new(16, 0, 8, 4)), Rect::new(16, 0, 8, 4));
+    }
+
+    #[test]
+    fn crop_and_paste_window_round_trip() {
+        let frame: Vec<u8> = (0..4 * 3).collect();
+        let rect = Rect::new(8, 1, 16, 2);
+        let window = crop_window(&frame, 32, &rect);
+        assert_eq!(window, [5, 6, 9, 10]);
+
+        let mut blank = vec![0xFF; frame.len()];
+        paste_window(&mut blank, 32, &rect, &window);
+        assert_eq!(blank, [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 5, 6, 0xFF, 0xFF, 9, 10, 0xFF]);
+    }
+
+    #[test]
+    fn gray_planes_split_and_merge() {
+        // Levels 3, 2, 1, 0 then 0, 1, 2, 3
+        let gray = [0b1110_0100, 0b0001_1011];
+        let (high, low) = split_gray_planes(&gray);
+        assert_eq!(high, [0b1100_0011]);
+        assert_eq!(low, [0b1010_0101]);
+        assert_eq!(merge_gray_planes(&high, &low), gray);
+    }
+
+    #[test]
+    fn window_outside_the_panel_is_rejected() {
+        assert!(validate_window(&Rect::new(8, 0, 16, 8), 32, 8).is_ok());
+        assert!(validate_window(&Rect::new(4, 0, 16, 8), 32, 8).is_err());
+        assert!(validate_window(&Rect::new(24, 0, 16, 8), 32, 8).is_err());
+        assert!(validate_window(&Rect::new(0, 4, 8, 8), 32, 8).is_err());
+        assert!(validate_window(&Rect::new(0, 0, 0, 8), 32, 8).is_err());
+    }
+}
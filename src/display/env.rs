@@ -0,0 +1,62 @@
+use crate::display::config::EpdConfig;
+use crate::display::driver::DisplayDriver;
+use crate::display::epd4in2::Epd4in2;
+use crate::display::error::Result;
+use crate::display::simulated::SimulatedDisplay;
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// Directory to write frames to instead of driving the hardware
+pub const ENV_SIM_DIR: &str = "EPAPER_SIM_DIR";
+/// File to record the controller traffic to, for the `replay_trace` binary
+pub const ENV_TRACE: &str = "EPAPER_TRACE";
+
+/// Open a panel, or a simulated one when `EPAPER_SIM_DIR` is set
+///
+/// On hardware, `EPAPER_TRACE=run.trace` records the controller traffic.
+/// The display can be moved to a `DisplayWorker`.
+pub fn open_display(config: EpdConfig) -> Result<Box<dyn DisplayDriver + Send>> {
+    open(config, env::var_os(ENV_SIM_DIR).map(PathBuf::from), env::var_os(ENV_TRACE))
+}
+
+/// Open several panels driven together, see `open_display`
+///
+/// The wiring is checked for shared pins first. Simulated panels write to
+/// a subdirectory named after the panel, and traces get the name appended,
+/// e.g. `run.trace.left`.
+pub fn open_displays(panels: &[(&str, EpdConfig)]) -> Result<Vec<Box<dyn DisplayDriver + Send>>> {
+    let configs: Vec<EpdConfig> = panels.iter().map(|(_, config)| config.clone()).collect();
+    EpdConfig::check_conflicts(&configs)?;
+
+    let sim_dir = env::var_os(ENV_SIM_DIR).map(PathBuf::from);
+    let trace = env::var_os(ENV_TRACE);
+    panels
+        .iter()
+        .map(|(name, config)| {
+            let trace = trace.as_ref().map(|path| {
+                let mut path = path.clone();
+                path.push(format!(".{}", name));
+                path
+            });
+            open(config.clone(), sim_dir.as_ref().map(|dir| dir.join(name)), trace)
+        })
+        .collect()
+}
+
+fn open(
+    config: EpdConfig,
+    sim_dir: Option<PathBuf>,
+    trace: Option<OsString>,
+) -> Result<Box<dyn DisplayDriver + Send>> {
+    if let Some(dir) = sim_dir {
+        let display = SimulatedDisplay::for_panel(config.panel, dir);
+        return Ok(Box::new(display));
+    }
+
+    let mut epd = Epd4in2::new(config)?;
+    if let Some(path) = trace {
+        epd.start_trace(path)?;
+    }
+    Ok(Box::new(epd))
+}
@@ -1,184 +1,1326 @@
-use anyhow::{anyhow, Result};
-use rppal::gpio::{Gpio, Level, OutputPin};
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
-use std::thread;
+use crate::display::driver::{
+    check_buffer_size, crop_window, frame_stride, paste_window, split_gray_planes,
+    validate_window, window_size, DisplayDriver, RefreshCapabilities,
+};
+use crate::display::error::{EpdError, Result};
+use crate::display::config::{EpdConfig, DEFAULT_BUSY_TIMEOUT, DEFAULT_CHUNK_SIZE};
+use crate::display::interface::EpdInterface;
+#[cfg(feature = "rppal")]
+use crate::display::interface::RppalInterface;
+use crate::display::lut::{temperature_factor, Waveform};
+use crate::display::panel::{Controller, InitStep, Panel, DEFAULT_PANEL};
+use crate::display::trace::{TraceRecord, TraceWriter};
+use crate::ui::Rect;
+use std::ops::RangeInclusive;
+use std::path::Path;
 use std::time::Duration;
 
-// Constants from epd4in2.h
+// Resolution of the default panel
 pub const EPD_WIDTH: u32 = 800;
 pub const EPD_HEIGHT: u32 = 480;
 
-// Command definitions
-const PANEL_SETTING: u8 = 0x00;
-const POWER_SETTING: u8 = 0x01;
-const POWER_OFF: u8 = 0x02;
-const POWER_OFF_SEQUENCE_SETTING: u8 = 0x03;
-const POWER_ON: u8 = 0x04;
-const POWER_ON_MEASURE: u8 = 0x05;
-const BOOSTER_SOFT_START: u8 = 0x06;
-const DEEP_SLEEP: u8 = 0x07;
-const DATA_START_TRANSMISSION_1: u8 = 0x10;
-const DATA_STOP: u8 = 0x11;
-const DISPLAY_REFRESH: u8 = 0x12;
-const DATA_START_TRANSMISSION_2: u8 = 0x13;
-const VCOM_AND_DATA_INTERVAL_SETTING: u8 = 0x50;
-const RESOLUTION_SETTING: u8 = 0x61;
-const VCM_DC_SETTING: u8 = 0x82;
-
-// Pin definitions from epdif.h
-const RST_PIN: u8 = 17;  // GPIO 17
-const DC_PIN: u8 = 25;   // GPIO 25
-const CS_PIN: u8 = 8;    // GPIO 8 (CE0)
-const BUSY_PIN: u8 = 24; // GPIO 24
-
-pub struct Epd4in2 {
-    width: u32,
-    height: u32,
-    spi: Spi,
-    reset_pin: OutputPin,
-    dc_pin: OutputPin,
-    busy_pin: rppal::gpio::InputPin,
+// Interval between reads of the BUSY line
+pub(crate) const BUSY_POLL_INTERVAL_MS: u64 = 100;
+
+// Hardware reset: level of the reset line and how long to hold it (ms)
+pub(crate) const RESET_PULSE: [(bool, u64); 3] = [(true, 200), (false, 10), (true, 200)];
+
+// Sensor readings outside this range (°C) are treated as bus errors
+const PLAUSIBLE_TEMPERATURE: RangeInclusive<f32> = -40.0..=85.0;
+
+// Command definitions (UC81xx)
+pub const PANEL_SETTING: u8 = 0x00;
+pub const POWER_SETTING: u8 = 0x01;
+pub const POWER_OFF: u8 = 0x02;
+pub const POWER_OFF_SEQUENCE_SETTING: u8 = 0x03;
+pub const POWER_ON: u8 = 0x04;
+pub const POWER_ON_MEASURE: u8 = 0x05;
+pub const BOOSTER_SOFT_START: u8 = 0x06;
+pub const DEEP_SLEEP: u8 = 0x07;
+pub const DATA_START_TRANSMISSION_1: u8 = 0x10;
+pub const DATA_STOP: u8 = 0x11;
+pub const DISPLAY_REFRESH: u8 = 0x12;
+pub const DATA_START_TRANSMISSION_2: u8 = 0x13;
+pub const LUT_FOR_VCOM: u8 = 0x20;
+pub const LUT_WHITE_TO_WHITE: u8 = 0x21;
+pub const LUT_BLACK_TO_WHITE: u8 = 0x22;
+pub const LUT_WHITE_TO_BLACK: u8 = 0x23;
+pub const LUT_BLACK_TO_BLACK: u8 = 0x24;
+pub const VCOM_AND_DATA_INTERVAL_SETTING: u8 = 0x50;
+pub const RESOLUTION_SETTING: u8 = 0x61;
+pub const VCM_DC_SETTING: u8 = 0x82;
+pub const PARTIAL_WINDOW: u8 = 0x90;
+pub const PARTIAL_IN: u8 = 0x91;
+pub const PARTIAL_OUT: u8 = 0x92;
+pub const TEMPERATURE_SENSOR_COMMAND: u8 = 0x40;
+pub const CASCADE_SETTING: u8 = 0xE0;
+pub const FORCE_TEMPERATURE: u8 = 0xE5;
+
+// Command definitions (SSD16xx)
+pub const SSD_DEEP_SLEEP: u8 = 0x10;
+pub const SSD_WRITE_TEMPERATURE: u8 = 0x1A;
+pub const SSD_READ_TEMPERATURE: u8 = 0x1B;
+pub const SSD_MASTER_ACTIVATION: u8 = 0x20;
+pub const SSD_DISPLAY_UPDATE_CONTROL_2: u8 = 0x22;
+pub const SSD_WRITE_RAM_BW: u8 = 0x24;
+pub const SSD_WRITE_RAM_RED: u8 = 0x26;
+pub const SSD_SET_RAM_X_RANGE: u8 = 0x44;
+pub const SSD_SET_RAM_Y_RANGE: u8 = 0x45;
+pub const SSD_SET_RAM_X_COUNTER: u8 = 0x4E;
+pub const SSD_SET_RAM_Y_COUNTER: u8 = 0x4F;
+
+// Update sequences for SSD_DISPLAY_UPDATE_CONTROL_2
+const SSD_UPDATE_FULL: u8 = 0xF7;
+const SSD_UPDATE_PARTIAL: u8 = 0xFF;
+const SSD_UPDATE_POWER_ON: u8 = 0xC0;
+const SSD_UPDATE_POWER_OFF: u8 = 0x83;
+const SSD_UPDATE_READ_TEMPERATURE: u8 = 0xB1;
+// Sequence bit that reloads the temperature from the sensor
+const SSD_LOAD_TEMPERATURE: u8 = 0x20;
+
+/// Power state of the panel as tracked by the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerState {
+    /// `init` has not completed since the driver was created
+    Uninitialized,
+    /// Powered on and ready for frames
+    Awake,
+    /// Booster and source drivers off, registers retained
+    PoweredOff,
+    /// Deep sleep, only a hardware reset wakes the controller
+    DeepSleep,
+}
+
+// What has to happen before a frame can be sent
+pub(crate) enum WakeStep {
+    Ready,
+    PowerOn,
+    Init,
+}
+
+// What `sleep` has to do in the current state
+pub(crate) enum SleepStep {
+    Asleep,
+    PowerOff,
+    DeepSleep,
+}
+
+/// Driver for Waveshare e-paper panels
+///
+/// Drives the 7.5" V2 panel unless another model is selected with
+/// `EpdConfig::panel` or `with_panel`. The hardware is reached through an
+/// `EpdInterface`: `RppalInterface` on a Raspberry Pi, `HalInterface` for
+/// any `embedded-hal` 1.0 implementation, or `MockInterface` in tests.
+pub struct Epd4in2<I: EpdInterface> {
+    panel: &'static Panel,
+    interface: I,
+    waveform: Waveform,
+    loaded_waveform: Waveform,
+    chunk_size: usize,
+    busy_timeout: Duration,
+    state: PowerState,
+    temperature: Option<f32>,
+    trace: Option<TraceWriter>,
+    // Frame on the panel, the old data for panels with register LUTs
+    shown_frame: Option<Vec<u8>>,
+}
+
+#[cfg(feature = "rppal")]
+impl Epd4in2<RppalInterface> {
+    /// Open the panel on a Raspberry Pi with the given wiring
+    pub fn new(config: EpdConfig) -> Result<Self> {
+        EpdConfig::check_conflicts(std::slice::from_ref(&config))?;
+        Ok(Self::with_config(RppalInterface::new(&config)?, &config))
+    }
+
+    /// Open several panels at once, e.g. on CE0 and CE1 of SPI0
+    ///
+    /// The wiring is checked for shared pins before anything is opened.
+    /// Panels on one bus can be driven from different threads: the kernel
+    /// spidev driver serializes the transfers and switches chip select.
+    pub fn open_all(configs: &[EpdConfig]) -> Result<Vec<Self>> {
+        EpdConfig::check_conflicts(configs)?;
+        configs
+            .iter()
+            .map(|config| Ok(Self::with_config(RppalInterface::new(config)?, config)))
+            .collect()
+    }
 }
 
 // Implementation of the core functionality
-impl Epd4in2 {
-    pub fn new() -> Result<Self> {
-        // Initialize SPI with correct settings
-        let spi = Spi::new(
-            Bus::Spi0,
-            SlaveSelect::Ss0,
-            10_000_000, // 10 MHz - you might need to adjust this based on stability
-            Mode::Mode0, // SPI mode 0
-        )?;
+impl<I: EpdInterface> Epd4in2<I> {
+    /// Drive the default panel through an arbitrary interface, e.g. a mock
+    pub fn with_interface(interface: I) -> Self {
+        Self::with_panel(interface, DEFAULT_PANEL)
+    }
+
+    /// Drive the given panel model through an arbitrary interface
+    pub fn with_panel(interface: I, panel: &'static Panel) -> Self {
+        Self {
+            panel,
+            interface,
+            waveform: Waveform::Full,
+            loaded_waveform: Waveform::Full,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            state: PowerState::Uninitialized,
+            temperature: None,
+            trace: None,
+            shown_frame: None,
+        }
+    }
 
-        // Initialize GPIO pins
-        let gpio = Gpio::new()?;
-        let reset_pin = gpio.get(RST_PIN)?.into_output();
-        let dc_pin = gpio.get(DC_PIN)?.into_output();
-        let busy_pin = gpio.get(BUSY_PIN)?.into_input();
+    /// Drive a panel through an arbitrary interface, taking the panel model,
+    /// chunk size and busy timeout from `config` (the pins are ignored)
+    pub fn with_config(interface: I, config: &EpdConfig) -> Self {
+        let mut epd = Self::with_panel(interface, config.panel);
+        epd.set_chunk_size(config.chunk_size);
+        epd.set_busy_timeout(config.busy_timeout);
+        epd
+    }
 
-        Ok(Self {
-            width: EPD_WIDTH,
-            height: EPD_HEIGHT,
-            spi,
-            reset_pin,
-            dc_pin,
-            busy_pin,
-        })
+    /// The panel model being driven
+    pub fn panel(&self) -> &'static Panel {
+        self.panel
     }
 
-    pub fn width(&self) -> u32 {
-        self.width
+    /// Current power state of the panel
+    pub fn power_state(&self) -> PowerState {
+        self.state
     }
 
-    pub fn height(&self) -> u32 {
-        self.height
+    /// Turn off the panel power while keeping the controller configured
+    ///
+    /// Cheaper to recover from than deep sleep: the next frame only needs a
+    /// POWER_ON instead of a reset and full init.
+    pub fn power_off(&mut self) -> Result<()> {
+        if self.power_off_needed()? {
+            self.start_power_off()?;
+            self.wait_until_idle()?;
+            self.finish_power_off();
+        }
+        Ok(())
     }
 
-    pub fn init(&mut self) -> Result<()> {
-        // Hardware reset
-        self.reset();
-        
-        // Initial commands for display setup
-        self.send_command(POWER_SETTING)?;
-        self.send_data(&[0x03, 0x00, 0x2B, 0x2B, 0x03])?;
+    // Make sure the panel can accept a frame, waking it if needed
+    fn ensure_awake(&mut self, operation: &'static str) -> Result<()> {
+        match self.wake_step(operation)? {
+            WakeStep::Ready => Ok(()),
+            WakeStep::PowerOn => {
+                self.start_power_on()?;
+                self.wait_until_idle()?;
+                self.finish_power_on();
+                Ok(())
+            }
+            WakeStep::Init => self.init(),
+        }
+    }
 
-        self.send_command(BOOSTER_SOFT_START)?;
-        self.send_data(&[0x17, 0x17, 0x17])?;
+    /// Set how long `wait_until_idle` waits before returning `BusyTimeout`
+    pub fn set_busy_timeout(&mut self, timeout: Duration) {
+        self.busy_timeout = timeout;
+    }
 
-        self.send_command(POWER_ON)?;
-        self.wait_until_idle();
+    /// How long `wait_until_idle` waits before returning `BusyTimeout`
+    pub fn busy_timeout(&self) -> Duration {
+        self.busy_timeout
+    }
 
-        self.send_command(PANEL_SETTING)?;
-        self.send_data(&[0xBF, 0x0D])?;
+    /// Set the maximum number of bytes sent in a single SPI transfer
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size.max(1);
+    }
 
-        self.send_command(RESOLUTION_SETTING)?;
-        self.send_data(&[
-            (EPD_WIDTH >> 8) as u8, 
-            (EPD_WIDTH & 0xFF) as u8,
-            (EPD_HEIGHT >> 8) as u8, 
-            (EPD_HEIGHT & 0xFF) as u8,
-        ])?;
+    /// Select the waveform loaded by the next call to `init`
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
 
-        self.send_command(VCM_DC_SETTING)?;
-        self.send_data(&[0x28])?;
+    /// The waveform selected with `set_waveform`
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
 
-        self.send_command(VCOM_AND_DATA_INTERVAL_SETTING)?;
-        self.send_data(&[0x97])?;
+    /// The waveform in the controller, used until the next `init`
+    pub fn loaded_waveform(&self) -> Waveform {
+        self.loaded_waveform
+    }
 
-        self.set_lut()?;
+    // Waveform a refresh would run with: the loaded one, or the selected one
+    // if `init` has to run first
+    fn refresh_waveform(&self) -> Waveform {
+        match self.state {
+            PowerState::Uninitialized | PowerState::DeepSleep => self.waveform,
+            _ => self.loaded_waveform,
+        }
+    }
 
+    /// Compensate refreshes for an ambient temperature in °C
+    ///
+    /// Panels with register LUTs get their frame counts scaled; panels using
+    /// the waveforms in their OTP are told to pick the waveform for this
+    /// temperature instead of reading their internal sensor. `None` goes
+    /// back to the defaults. Takes effect immediately if the panel is awake,
+    /// otherwise on the next `init`.
+    pub fn set_temperature(&mut self, celsius: Option<f32>) -> Result<()> {
+        self.temperature = celsius;
+        if self.state == PowerState::Awake {
+            self.apply_temperature()?;
+        }
         Ok(())
     }
 
+    /// The temperature set with `set_temperature`
+    pub fn temperature(&self) -> Option<f32> {
+        self.temperature
+    }
+
+    /// Read the controller's internal temperature sensor in °C
+    ///
+    /// Needs an interface that can read from the controller (see
+    /// `EpdInterface::spi_read` and `EpdConfig::miso_wired`). Readings
+    /// outside -40..85°C are returned as `ImplausibleTemperature`. Pass the
+    /// result to `set_temperature` to compensate panels with register LUTs.
+    pub fn read_temperature(&mut self) -> Result<f32> {
+        self.ensure_awake("read the temperature")?;
+        let mut raw = [0u8; 2];
+
+        let celsius = match self.panel.controller {
+            Controller::Uc81xx => {
+                self.send_command(TEMPERATURE_SENSOR_COMMAND)?;
+                self.wait_until_idle()?;
+                self.read_data(&mut raw)?;
+                // 11 bit two's complement in 1/8 °C
+                (((raw[0] as i8 as i16) << 3) | (raw[1] >> 5) as i16) as f32 / 8.0
+            }
+            Controller::Ssd16xx => {
+                self.activate(SSD_UPDATE_READ_TEMPERATURE)?;
+                self.wait_until_idle()?;
+                self.send_command(SSD_READ_TEMPERATURE)?;
+                self.read_data(&mut raw)?;
+                // The sensor reading replaced a temperature we wrote
+                if self.temperature.is_some() {
+                    self.apply_temperature()?;
+                }
+                // 12 bit two's complement in 1/16 °C
+                (((raw[0] as i8 as i16) << 4) | (raw[1] >> 4) as i16) as f32 / 16.0
+            }
+        };
+
+        log::debug!("Controller temperature {:.1}°C", celsius);
+        if !PLAUSIBLE_TEMPERATURE.contains(&celsius) {
+            return Err(EpdError::ImplausibleTemperature(celsius));
+        }
+        Ok(celsius)
+    }
+
+    // Load compensated LUTs or hand the temperature to the controller
+    fn apply_temperature(&mut self) -> Result<()> {
+        if self.panel.custom_luts {
+            return self.set_lut(self.loaded_waveform);
+        }
+
+        match (self.panel.controller, self.temperature) {
+            (Controller::Uc81xx, Some(celsius)) => {
+                self.send_command(CASCADE_SETTING)?;
+                self.send_data(&[0x02])?; // Use the forced temperature
+                self.send_command(FORCE_TEMPERATURE)?;
+                self.send_data(&[celsius.round().clamp(-128.0, 127.0) as i8 as u8])?;
+            }
+            (Controller::Uc81xx, None) => {
+                self.send_command(CASCADE_SETTING)?;
+                self.send_data(&[0x00])?;
+            }
+            (Controller::Ssd16xx, Some(celsius)) => {
+                let raw = (celsius * 16.0).round().clamp(-2048.0, 2047.0) as i16;
+                self.send_command(SSD_WRITE_TEMPERATURE)?;
+                self.send_data(&[(raw >> 4) as u8, ((raw & 0x0F) << 4) as u8])?;
+            }
+            // The update sequences read the internal sensor again
+            (Controller::Ssd16xx, None) => {}
+        }
+        Ok(())
+    }
+
+    // SSD16xx update sequence, keeping a written temperature if there is one
+    fn update_sequence(&self, sequence: u8) -> u8 {
+        match self.temperature {
+            Some(_) => sequence & !SSD_LOAD_TEMPERATURE,
+            None => sequence,
+        }
+    }
+
+    /// Record all traffic with the controller to a trace file
+    ///
+    /// Every command and data byte, reset pin change, delay and BUSY wait
+    /// (with its duration) is written until `stop_trace`. Replay the file
+    /// with the `replay_trace` binary. The file is flushed around every BUSY
+    /// wait, so a refresh that hangs or a killed process still leaves a
+    /// usable trace. A trace that can no longer be written is dropped with a
+    /// warning rather than failing the refresh.
+    pub fn start_trace<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.trace = Some(TraceWriter::create(path, self.panel.name)?);
+        Ok(())
+    }
+
+    /// Flush and close the trace file, if any
+    pub fn stop_trace(&mut self) -> Result<()> {
+        match self.trace.take() {
+            Some(mut trace) => trace.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether a trace is being recorded
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    // Append a record to the trace; the record is only built while tracing
+    pub(crate) fn record<F: FnOnce() -> TraceRecord>(&mut self, record: F) {
+        if let Some(trace) = self.trace.as_mut() {
+            if let Err(error) = trace.record(&record()) {
+                log::warn!("Stopping trace: {}", error);
+                self.trace = None;
+            }
+        }
+    }
+
+    // Write the buffered trace to the file, e.g. before a wait that may
+    // never end
+    pub(crate) fn flush_trace(&mut self) {
+        if let Some(trace) = self.trace.as_mut() {
+            if let Err(error) = trace.flush() {
+                log::warn!("Stopping trace: {}", error);
+                self.trace = None;
+            }
+        }
+    }
+
+    /// Access the underlying interface
+    pub fn interface(&self) -> &I {
+        &self.interface
+    }
+
+    /// Mutable access to the underlying interface
+    pub fn interface_mut(&mut self) -> &mut I {
+        &mut self.interface
+    }
+
+    /// Release the underlying interface
+    pub fn into_interface(self) -> I {
+        self.interface
+    }
+
     // Reset the display
-    pub fn reset(&mut self) {
-        self.reset_pin.write(Level::High);
-        thread::sleep(Duration::from_millis(200));
-        self.reset_pin.write(Level::Low);
-        thread::sleep(Duration::from_millis(10));
-        self.reset_pin.write(Level::High);
-        thread::sleep(Duration::from_millis(200));
+    pub fn reset(&mut self) -> Result<()> {
+        for (high, ms) in RESET_PULSE {
+            self.set_reset_line(high)?;
+            self.delay_ms(ms);
+        }
+        Ok(())
+    }
+
+    // Drive the reset line
+    pub(crate) fn set_reset_line(&mut self, high: bool) -> Result<()> {
+        self.record(|| TraceRecord::Reset(high));
+        self.interface.set_reset(high)
+    }
+
+    // Fixed delay between commands
+    pub(crate) fn delay_ms(&mut self, ms: u64) {
+        self.record(|| TraceRecord::Delay(ms));
+        self.interface.delay_ms(ms);
+    }
+
+    /// Whether the controller reports busy on the BUSY line
+    pub fn is_busy(&mut self) -> Result<bool> {
+        Ok(self.interface.read_busy()? == self.panel.controller.busy_level())
     }
 
     // Wait until the busy pin is released
-    pub fn wait_until_idle(&self) {
+    pub fn wait_until_idle(&mut self) -> Result<()> {
         log::debug!("Waiting for display to be ready...");
-        while self.busy_pin.read() == Level::Low {
-            thread::sleep(Duration::from_millis(100));
+        self.flush_trace();
+        let mut waited = Duration::ZERO;
+        while self.is_busy()? {
+            if waited >= self.busy_timeout {
+                log::error!("Display still busy after {:?}", waited);
+                self.record(|| TraceRecord::Wait(waited));
+                return Err(EpdError::BusyTimeout(waited));
+            }
+            self.interface.delay_ms(BUSY_POLL_INTERVAL_MS);
+            waited += Duration::from_millis(BUSY_POLL_INTERVAL_MS);
         }
+        self.record(|| TraceRecord::Wait(waited));
         log::debug!("Display is ready.");
+        Ok(())
     }
 
     // Send a command to the display
     pub fn send_command(&mut self, command: u8) -> Result<()> {
-        self.dc_pin.write(Level::Low);
-        self.spi.write(&[command])?;
+        self.record(|| TraceRecord::Command(command));
+        self.interface.set_dc(false)?;
+        self.interface.spi_write(&[command])?;
         Ok(())
     }
 
     // Send data to the display
     pub fn send_data(&mut self, data: &[u8]) -> Result<()> {
-        self.dc_pin.write(Level::High);
-        self.spi.write(data)?;
+        self.record(|| TraceRecord::Data(data.to_vec()));
+        self.interface.set_dc(true)?;
+        self.interface.spi_write(data)?;
         Ok(())
     }
 
-    // Display a frame from the buffer
-    pub fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
-        if frame_buffer.len() != (self.width * self.height / 8) as usize {
-            return Err(anyhow!("Frame buffer size mismatch"));
-        }
+    // Read data bytes from the controller
+    fn read_data(&mut self, buffer: &mut [u8]) -> Result<()> {
+        self.interface.set_dc(true)?;
+        self.interface.spi_read(buffer)?;
+        self.record(|| TraceRecord::Read(buffer.to_vec()));
+        Ok(())
+    }
 
-        let buffer_size = 4000; // Break into chunks for sending
-        
-        self.send_command(DATA_START_TRANSMISSION_1)?;
-        
-        for chunk in frame_buffer.chunks(buffer_size) {
+    // Send a command followed by a large buffer, split into chunks
+    fn send_buffer(&mut self, command: u8, buffer: &[u8]) -> Result<()> {
+        self.send_command(command)?;
+        for chunk in buffer.chunks(self.chunk_size) {
             self.send_data(chunk)?;
         }
+        Ok(())
+    }
 
-        self.send_command(DISPLAY_REFRESH)?;
-        self.wait_until_idle();
+    // Send the new image to DTM2 in the polarity the panel expects (UC81xx)
+    fn send_new_data(&mut self, buffer: &[u8]) -> Result<()> {
+        if self.panel.inverted_data {
+            let inverted: Vec<u8> = buffer.iter().map(|byte| !byte).collect();
+            self.send_buffer(DATA_START_TRANSMISSION_2, &inverted)
+        } else {
+            self.send_buffer(DATA_START_TRANSMISSION_2, buffer)
+        }
+    }
 
+    // Run an SSD16xx update sequence
+    fn activate(&mut self, sequence: u8) -> Result<()> {
+        self.send_command(SSD_DISPLAY_UPDATE_CONTROL_2)?;
+        self.send_data(&[sequence])?;
+        self.send_command(SSD_MASTER_ACTIVATION)?;
         Ok(())
     }
 
-    // Put display to sleep to save power
-    pub fn sleep(&mut self) -> Result<()> {
-        self.send_command(POWER_OFF)?;
-        self.wait_until_idle();
-        self.send_command(DEEP_SLEEP)?;
-        self.send_data(&[0xA5])?;
-        
+    // Select the window used by the next data transmission (UC81xx)
+    fn set_partial_window(&mut self, rect: &Rect) -> Result<()> {
+        let x_start = rect.x as u32;
+        let x_end = x_start + rect.width as u32 - 1;
+        let y_start = rect.y as u32;
+        let y_end = y_start + rect.height as u32 - 1;
+
+        self.send_command(PARTIAL_WINDOW)?;
+        self.send_data(&[
+            (x_start >> 8) as u8,
+            (x_start & 0xF8) as u8,
+            (x_end >> 8) as u8,
+            ((x_end & 0xF8) | 0x07) as u8,
+            (y_start >> 8) as u8,
+            (y_start & 0xFF) as u8,
+            (y_end >> 8) as u8,
+            (y_end & 0xFF) as u8,
+            0x01, // Gates scan both inside and outside of the window
+        ])?;
+        Ok(())
+    }
+
+    // Select the RAM area written by the next data transmission (SSD16xx)
+    fn set_ram_window(&mut self, rect: &Rect) -> Result<()> {
+        let x_start = (rect.x / 8) as u8;
+        let x_end = ((rect.x + rect.width - 1) / 8) as u8;
+        let y_start = rect.y as u32;
+        let y_end = (rect.y + rect.height - 1) as u32;
+
+        self.send_command(SSD_SET_RAM_X_RANGE)?;
+        self.send_data(&[x_start, x_end])?;
+        self.send_command(SSD_SET_RAM_Y_RANGE)?;
+        self.send_data(&[
+            (y_start & 0xFF) as u8,
+            (y_start >> 8) as u8,
+            (y_end & 0xFF) as u8,
+            (y_end >> 8) as u8,
+        ])?;
+
+        self.send_command(SSD_SET_RAM_X_COUNTER)?;
+        self.send_data(&[x_start])?;
+        self.send_command(SSD_SET_RAM_Y_COUNTER)?;
+        self.send_data(&[(y_start & 0xFF) as u8, (y_start >> 8) as u8])?;
+        Ok(())
+    }
+
+    // The whole RAM area as a window
+    fn full_window(&self) -> Rect {
+        Rect::new(
+            0,
+            0,
+            frame_stride(self.panel.width) as i32 * 8,
+            self.panel.height as i32,
+        )
+    }
+
+    // Load the lookup tables of a waveform, compensated for the temperature
+    fn set_lut(&mut self, waveform: Waveform) -> Result<()> {
+        let lut = match self.temperature {
+            Some(celsius) => waveform.lut().scaled(temperature_factor(celsius)),
+            None => waveform.lut().clone(),
+        };
+
+        self.send_command(LUT_FOR_VCOM)?;
+        self.send_data(&lut.vcom)?;
+
+        self.send_command(LUT_WHITE_TO_WHITE)?;
+        self.send_data(&lut.ww)?;
+
+        self.send_command(LUT_BLACK_TO_WHITE)?;
+        self.send_data(&lut.bw)?;
+
+        self.send_command(LUT_WHITE_TO_BLACK)?;
+        self.send_data(&lut.wb)?;
+
+        self.send_command(LUT_BLACK_TO_BLACK)?;
+        self.send_data(&lut.bb)?;
+
+        self.loaded_waveform = waveform;
+        Ok(())
+    }
+}
+
+// Refresh operations split at the point where the controller goes busy,
+// so the blocking and async drivers share the command sequences
+impl<I: EpdInterface> Epd4in2<I> {
+    // Mark the panel uninitialized and check the selected waveform
+    pub(crate) fn begin_init(&mut self) -> Result<()> {
+        self.state = PowerState::Uninitialized;
+        if !self.panel.supports_waveform(self.waveform) {
+            return Err(EpdError::Unsupported("the selected waveform"));
+        }
+        log::debug!("Initializing {}", self.panel.description);
+        Ok(())
+    }
+
+    // Load the LUTs and temperature once the init sequence has run
+    pub(crate) fn finish_init(&mut self) -> Result<()> {
+        if self.panel.custom_luts {
+            self.set_lut(self.waveform)?;
+        } else if self.temperature.is_some() {
+            self.apply_temperature()?;
+        }
+        self.state = PowerState::Awake;
+        Ok(())
+    }
+
+    // Send a command of the panel's init sequence
+    pub(crate) fn send_init_command(&mut self, command: u8, data: &[u8]) -> Result<()> {
+        self.send_command(command)?;
+        if !data.is_empty() {
+            self.send_data(data)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn wake_step(&self, operation: &'static str) -> Result<WakeStep> {
+        match self.state {
+            PowerState::Awake => Ok(WakeStep::Ready),
+            PowerState::PoweredOff => {
+                log::debug!("Powering display back on");
+                Ok(WakeStep::PowerOn)
+            }
+            PowerState::DeepSleep => {
+                log::info!("Waking display from deep sleep");
+                Ok(WakeStep::Init)
+            }
+            state => Err(EpdError::InvalidState { operation, state }),
+        }
+    }
+
+    // Whether `power_off` has to switch the power off
+    pub(crate) fn power_off_needed(&self) -> Result<bool> {
+        match self.state {
+            PowerState::Awake => Ok(true),
+            PowerState::PoweredOff => Ok(false),
+            state => Err(EpdError::InvalidState {
+                operation: "power off",
+                state,
+            }),
+        }
+    }
+
+    pub(crate) fn sleep_step(&self) -> Result<SleepStep> {
+        match self.state {
+            PowerState::DeepSleep => Ok(SleepStep::Asleep),
+            PowerState::Awake => Ok(SleepStep::PowerOff),
+            PowerState::PoweredOff => Ok(SleepStep::DeepSleep),
+            state => Err(EpdError::InvalidState {
+                operation: "sleep",
+                state,
+            }),
+        }
+    }
+
+    pub(crate) fn start_power_on(&mut self) -> Result<()> {
+        match self.panel.controller {
+            Controller::Uc81xx => self.send_command(POWER_ON),
+            Controller::Ssd16xx => self.activate(SSD_UPDATE_POWER_ON),
+        }
+    }
+
+    pub(crate) fn finish_power_on(&mut self) {
+        self.state = PowerState::Awake;
+    }
+
+    pub(crate) fn start_power_off(&mut self) -> Result<()> {
+        match self.panel.controller {
+            Controller::Uc81xx => self.send_command(POWER_OFF),
+            Controller::Ssd16xx => self.activate(SSD_UPDATE_POWER_OFF),
+        }
+    }
+
+    pub(crate) fn finish_power_off(&mut self) {
+        self.state = PowerState::PoweredOff;
+    }
+
+    // Send the deep sleep command; the panel must be powered off
+    pub(crate) fn enter_deep_sleep(&mut self) -> Result<()> {
+        match self.panel.controller {
+            Controller::Uc81xx => {
+                self.send_command(DEEP_SLEEP)?;
+                self.send_data(&[0xA5])?;
+            }
+            Controller::Ssd16xx => {
+                self.send_command(SSD_DEEP_SLEEP)?;
+                self.send_data(&[0x01])?;
+            }
+        }
+        self.state = PowerState::DeepSleep;
+        Ok(())
+    }
+
+    pub(crate) fn check_color_frame(&self, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        if !self.panel.color.is_tricolor() {
+            return Err(EpdError::Unsupported("color frames"));
+        }
+        check_buffer_size(black, self.frame_size())?;
+        check_buffer_size(chromatic, self.frame_size())
+    }
+
+    pub(crate) fn check_gray_frame(&self, gray_buffer: &[u8]) -> Result<()> {
+        check_buffer_size(gray_buffer, self.frame_size() * 2)?;
+        let loaded = self.refresh_waveform();
+        if loaded != Waveform::Gray4 {
+            return Err(EpdError::WaveformMismatch {
+                required: Waveform::Gray4,
+                loaded,
+            });
+        }
+        Ok(())
+    }
+
+    // Transmit a frame and start the refresh without waiting for it
+    pub(crate) fn start_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+        if self.panel.color.is_tricolor() {
+            // Clear the color plane so no red/yellow from an earlier frame remains
+            let blank = vec![0xFF; frame_buffer.len()];
+            return self.start_color_frame(frame_buffer, &blank);
+        }
+
+        match self.panel.controller {
+            Controller::Uc81xx => {
+                // Register LUTs drive each pixel by its old and new value
+                let shown = self.shown_frame.take();
+                if self.panel.custom_luts {
+                    let old = shown.unwrap_or_else(|| vec![0xFF; frame_buffer.len()]);
+                    self.send_buffer(DATA_START_TRANSMISSION_1, &old)?;
+                }
+                self.send_new_data(frame_buffer)?;
+                self.send_command(DISPLAY_REFRESH)?;
+                if self.panel.custom_luts {
+                    self.shown_frame = Some(frame_buffer.to_vec());
+                }
+            }
+            Controller::Ssd16xx => {
+                // Write both RAMs so the next partial refresh has a base image
+                let window = self.full_window();
+                self.set_ram_window(&window)?;
+                self.send_buffer(SSD_WRITE_RAM_BW, frame_buffer)?;
+                self.set_ram_window(&window)?;
+                self.send_buffer(SSD_WRITE_RAM_RED, frame_buffer)?;
+                self.activate(self.update_sequence(SSD_UPDATE_FULL))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn start_color_frame(&mut self, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        match self.panel.controller {
+            Controller::Uc81xx => {
+                self.send_buffer(DATA_START_TRANSMISSION_1, black)?;
+                self.send_buffer(DATA_START_TRANSMISSION_2, chromatic)?;
+                self.send_command(DISPLAY_REFRESH)?;
+            }
+            Controller::Ssd16xx => {
+                // The red RAM uses a set bit for a colored pixel
+                let red: Vec<u8> = chromatic.iter().map(|byte| !byte).collect();
+                let window = self.full_window();
+                self.set_ram_window(&window)?;
+                self.send_buffer(SSD_WRITE_RAM_BW, black)?;
+                self.set_ram_window(&window)?;
+                self.send_buffer(SSD_WRITE_RAM_RED, &red)?;
+                self.activate(self.update_sequence(SSD_UPDATE_FULL))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_partial(&self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        if !self.panel.partial_refresh {
+            return Err(EpdError::Unsupported("partial refresh"));
+        }
+        validate_window(rect, self.panel.width, self.panel.height)?;
+        check_buffer_size(buffer, window_size(rect))?;
+        if self.state == PowerState::DeepSleep {
+            log::warn!("Partial refresh after deep sleep, the rest of the panel is not redrawn");
+        }
         Ok(())
     }
 
-    // Set the lookup table for waveform
-    fn set_lut(&mut self) -> Result<()> {
-        // You would implement the LUT (Look-Up Table) setting here
-        // This is specific to the display controller and waveform
-        // For now, we'll just return OK
+    // Transmit a window and start its refresh, see `finish_partial`
+    pub(crate) fn start_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        match self.panel.controller {
+            Controller::Uc81xx => {
+                let shown = self.shown_frame.take();
+                self.send_command(PARTIAL_IN)?;
+                self.set_partial_window(rect)?;
+                if self.panel.custom_luts {
+                    let old = match &shown {
+                        Some(frame) => crop_window(frame, self.panel.width, rect),
+                        None => vec![0xFF; buffer.len()],
+                    };
+                    self.send_buffer(DATA_START_TRANSMISSION_1, &old)?;
+                }
+                self.send_new_data(buffer)?;
+                self.send_command(DISPLAY_REFRESH)?;
+
+                if let Some(mut frame) = shown {
+                    paste_window(&mut frame, self.panel.width, rect, buffer);
+                    self.shown_frame = Some(frame);
+                }
+            }
+            Controller::Ssd16xx => {
+                self.set_ram_window(rect)?;
+                self.send_buffer(SSD_WRITE_RAM_BW, buffer)?;
+                self.activate(self.update_sequence(SSD_UPDATE_PARTIAL))?;
+            }
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    // Leave partial mode once the refresh of the window is done
+    pub(crate) fn finish_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        match self.panel.controller {
+            Controller::Uc81xx => self.send_command(PARTIAL_OUT),
+            Controller::Ssd16xx => {
+                // Keep the base image in sync for the next partial refresh
+                self.set_ram_window(rect)?;
+                self.send_buffer(SSD_WRITE_RAM_RED, buffer)
+            }
+        }
+    }
+
+    pub(crate) fn start_gray_frame(&mut self, gray_buffer: &[u8]) -> Result<()> {
+        let (high, low) = split_gray_planes(gray_buffer);
+        // The gray levels are not a black/white old image for the next frame
+        self.shown_frame = None;
+
+        self.send_buffer(DATA_START_TRANSMISSION_1, &high)?;
+        self.send_buffer(DATA_START_TRANSMISSION_2, &low)?;
+        self.send_command(DISPLAY_REFRESH)
+    }
+}
+
+impl<I: EpdInterface> DisplayDriver for Epd4in2<I> {
+    fn width(&self) -> u32 {
+        self.panel.width
+    }
+
+    fn height(&self) -> u32 {
+        self.panel.height
+    }
+
+    fn capabilities(&self) -> RefreshCapabilities {
+        self.panel.capabilities()
+    }
+
+    fn init(&mut self) -> Result<()> {
+        self.begin_init()?;
+
+        // Hardware reset
+        self.reset()?;
+
+        // Initial commands for display setup
+        for step in self.panel.init_sequence {
+            match *step {
+                InitStep::Command(command, data) => self.send_init_command(command, data)?,
+                InitStep::WaitIdle => self.wait_until_idle()?,
+                InitStep::Delay(ms) => self.delay_ms(ms),
+            }
+        }
+
+        self.finish_init()
+    }
+
+    // Display a frame from the buffer
+    fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+        check_buffer_size(frame_buffer, self.frame_size())?;
+        self.ensure_awake("display a frame")?;
+
+        self.start_frame(frame_buffer)?;
+        self.wait_until_idle()?;
+
+        Ok(())
+    }
+
+    // Refresh only the given window of the display
+    fn display_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        self.check_partial(rect, buffer)?;
+        self.ensure_awake("display a partial frame")?;
+
+        self.start_partial(rect, buffer)?;
+        self.wait_until_idle()?;
+        self.finish_partial(rect, buffer)
+    }
+
+    // Display a 4-level grayscale frame, needs the Gray4 waveform
+    fn display_gray_frame(&mut self, gray_buffer: &[u8]) -> Result<()> {
+        self.check_gray_frame(gray_buffer)?;
+        self.ensure_awake("display a grayscale frame")?;
+
+        self.start_gray_frame(gray_buffer)?;
+        self.wait_until_idle()?;
+
+        Ok(())
+    }
+
+    // Display a frame on a black/white/red or black/white/yellow panel
+    fn display_color_frame(&mut self, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        self.check_color_frame(black, chromatic)?;
+        self.ensure_awake("display a color frame")?;
+
+        self.start_color_frame(black, chromatic)?;
+        self.wait_until_idle()?;
+
+        Ok(())
+    }
+
+    // Put display to sleep to save power
+    fn sleep(&mut self) -> Result<()> {
+        match self.sleep_step()? {
+            SleepStep::Asleep => return Ok(()),
+            SleepStep::PowerOff => self.power_off()?,
+            SleepStep::DeepSleep => {}
+        }
+
+        self.enter_deep_sleep()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::mock::{MockEvent, MockInterface};
+    use crate::display::panel::{EPD_2IN13_V2, EPD_4IN2, EPD_7IN5_V2, PANELS};
+
+    // Mock whose BUSY line idles at the level the panel's controller uses
+    fn mock_for(panel: &Panel) -> MockInterface {
+        let mut mock = MockInterface::new();
+        mock.set_idle_level(!panel.controller.busy_level());
+        mock
+    }
+
+    // Initialized driver with the init traffic cleared
+    fn awake(panel: &'static Panel) -> Epd4in2<MockInterface> {
+        let mut epd = Epd4in2::with_panel(mock_for(panel), panel);
+        epd.init().unwrap();
+        epd.interface_mut().clear();
+        epd
+    }
+
+    // A frame whose bytes differ from their neighbours and their inverse
+    fn pattern(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i * 7 + 3) as u8).collect()
+    }
+
+    fn inverted(buffer: &[u8]) -> Vec<u8> {
+        buffer.iter().map(|byte| !byte).collect()
+    }
+
+    #[test]
+    fn display_frame_7in5_v2_sends_inverted_frame_to_dtm2() {
+        let mut epd = awake(&EPD_7IN5_V2);
+        let frame = pattern(epd.frame_size());
+        epd.display_frame(&frame).unwrap();
+
+        let mock = epd.interface();
+        assert_eq!(mock.commands(), [DATA_START_TRANSMISSION_2, DISPLAY_REFRESH]);
+        assert_eq!(mock.data_for(DATA_START_TRANSMISSION_2), [inverted(&frame)]);
+    }
+
+    #[test]
+    fn display_frame_4in2_sends_old_and_new_planes() {
+        let mut epd = awake(&EPD_4IN2);
+        let first = pattern(epd.frame_size());
+        let second = inverted(&first);
+        epd.display_frame(&first).unwrap();
+        epd.display_frame(&second).unwrap();
+
+        let mock = epd.interface();
+        assert_eq!(
+            mock.commands(),
+            [
+                DATA_START_TRANSMISSION_1,
+                DATA_START_TRANSMISSION_2,
+                DISPLAY_REFRESH,
+                DATA_START_TRANSMISSION_1,
+                DATA_START_TRANSMISSION_2,
+                DISPLAY_REFRESH,
+            ]
+        );
+        // Nothing is known to be on the panel before the first frame
+        let white = vec![0xFF; first.len()];
+        assert_eq!(mock.data_for(DATA_START_TRANSMISSION_1), [white, first.clone()]);
+        assert_eq!(mock.data_for(DATA_START_TRANSMISSION_2), [first, second]);
+    }
+
+    #[test]
+    fn display_frame_on_tricolor_uc81xx_clears_the_color_plane() {
+        for panel in PANELS.iter().filter(|panel| {
+            panel.controller == Controller::Uc81xx && panel.color.is_tricolor()
+        }) {
+            let mut epd = awake(panel);
+            let frame = pattern(epd.frame_size());
+            epd.display_frame(&frame).unwrap();
+
+            let mock = epd.interface();
+            assert_eq!(
+                mock.commands(),
+                [DATA_START_TRANSMISSION_1, DATA_START_TRANSMISSION_2, DISPLAY_REFRESH],
+                "{}",
+                panel.name
+            );
+            assert_eq!(mock.data_for(DATA_START_TRANSMISSION_2), [vec![0xFF; frame.len()]]);
+            assert_eq!(mock.data_for(DATA_START_TRANSMISSION_1), [frame]);
+        }
+    }
+
+    #[test]
+    fn display_frame_on_ssd16xx_writes_both_rams() {
+        for panel in PANELS.iter().filter(|panel| panel.controller == Controller::Ssd16xx) {
+            let mut epd = awake(panel);
+            let frame = pattern(epd.frame_size());
+            epd.display_frame(&frame).unwrap();
+
+            let mock = epd.interface();
+            let window = [
+                SSD_SET_RAM_X_RANGE,
+                SSD_SET_RAM_Y_RANGE,
+                SSD_SET_RAM_X_COUNTER,
+                SSD_SET_RAM_Y_COUNTER,
+            ];
+            let mut expected = window.to_vec();
+            expected.push(SSD_WRITE_RAM_BW);
+            expected.extend(window);
+            expected.push(SSD_WRITE_RAM_RED);
+            expected.extend([SSD_DISPLAY_UPDATE_CONTROL_2, SSD_MASTER_ACTIVATION]);
+            assert_eq!(mock.commands(), expected, "{}", panel.name);
+
+            // The red RAM takes a set bit for red on tri-color panels
+            let red = match panel.color.is_tricolor() {
+                true => vec![0x00; frame.len()],
+                false => frame.clone(),
+            };
+            assert_eq!(mock.data_for(SSD_WRITE_RAM_BW), [frame]);
+            assert_eq!(mock.data_for(SSD_WRITE_RAM_RED), [red]);
+            assert_eq!(mock.data_for(SSD_DISPLAY_UPDATE_CONTROL_2), [vec![SSD_UPDATE_FULL]]);
+        }
+    }
+
+    #[test]
+    fn partial_refresh_on_4in2_sends_the_old_window() {
+        let mut epd = awake(&EPD_4IN2);
+        let frame = pattern(epd.frame_size());
+        epd.display_frame(&frame).unwrap();
+        epd.interface_mut().clear();
+
+        let rect = Rect::new(8, 2, 16, 3);
+        let window = vec![0x00; window_size(&rect)];
+        epd.display_partial(&rect, &window).unwrap();
+        epd.display_frame(&frame).unwrap();
+
+        let mock = epd.interface();
+        let dtm1 = mock.data_for(DATA_START_TRANSMISSION_1);
+        assert_eq!(dtm1[0], crop_window(&frame, EPD_4IN2.width, &rect));
+        // The next full refresh starts from the frame with the window applied
+        let mut shown = frame.clone();
+        paste_window(&mut shown, EPD_4IN2.width, &rect, &window);
+        assert_eq!(dtm1[1], shown);
+    }
+
+    #[test]
+    fn partial_refresh_on_7in5_v2_sends_the_inverted_window() {
+        let mut epd = awake(&EPD_7IN5_V2);
+        let rect = Rect::new(8, 10, 16, 4);
+        let window = pattern(window_size(&rect));
+        epd.display_partial(&rect, &window).unwrap();
+
+        let mock = epd.interface();
+        assert!(mock.data_for(DATA_START_TRANSMISSION_1).is_empty());
+        assert_eq!(mock.data_for(DATA_START_TRANSMISSION_2), [inverted(&window)]);
+    }
+
+    #[test]
+    fn gray_frame_needs_the_loaded_waveform() {
+        let mut epd = awake(&EPD_4IN2);
+        epd.set_waveform(Waveform::Gray4);
+        let gray = vec![0xFF; epd.frame_size() * 2];
+
+        let error = epd.display_gray_frame(&gray).unwrap_err();
+        assert!(matches!(
+            error,
+            EpdError::WaveformMismatch {
+                required: Waveform::Gray4,
+                loaded: Waveform::Full,
+            }
+        ));
+        assert!(epd.interface().commands().is_empty());
+
+        epd.init().unwrap();
+        assert_eq!(epd.loaded_waveform(), Waveform::Gray4);
+        epd.display_gray_frame(&gray).unwrap();
+    }
+
+    #[test]
+    fn gray_frame_after_deep_sleep_loads_the_selected_waveform() {
+        let mut epd = awake(&EPD_4IN2);
+        epd.sleep().unwrap();
+        epd.set_waveform(Waveform::Gray4);
+        epd.interface_mut().clear();
+
+        epd.display_gray_frame(&vec![0xFF; epd.frame_size() * 2]).unwrap();
+        let lut = epd.interface().data_for(LUT_WHITE_TO_WHITE);
+        assert_eq!(lut, [Waveform::Gray4.lut().ww.to_vec()]);
+    }
+
+    #[test]
+    fn set_temperature_reloads_the_loaded_waveform() {
+        let mut epd = awake(&EPD_4IN2);
+        epd.set_waveform(Waveform::Fast);
+        epd.set_temperature(Some(5.0)).unwrap();
+
+        let expected = Waveform::Full.lut().scaled(temperature_factor(5.0));
+        let mock = epd.interface();
+        assert_eq!(mock.data_for(LUT_WHITE_TO_WHITE), [expected.ww.to_vec()]);
+        assert_eq!(epd.loaded_waveform(), Waveform::Full);
+    }
+
+    #[test]
+    fn read_temperature_decodes_the_sensor_value() {
+        let mut epd = awake(&EPD_4IN2);
+        epd.interface_mut().script_read(&[0x19, 0x80]);
+        assert_eq!(epd.read_temperature().unwrap(), 25.5);
+    }
+
+    #[test]
+    fn read_temperature_rejects_implausible_values() {
+        let mut epd = awake(&EPD_4IN2);
+        epd.interface_mut().script_read(&[0x7F, 0xE0]);
+        assert!(matches!(
+            epd.read_temperature(),
+            Err(EpdError::ImplausibleTemperature(_))
+        ));
+    }
+
+    #[test]
+    fn init_resets_and_runs_the_panel_sequence() {
+        for panel in PANELS.iter().copied() {
+            let mut epd = Epd4in2::with_panel(mock_for(panel), panel);
+            epd.init().unwrap();
+            let mock = epd.interface();
+
+            assert_eq!(
+                mock.events()[..6],
+                [
+                    MockEvent::Reset(true),
+                    MockEvent::Delay(200),
+                    MockEvent::Reset(false),
+                    MockEvent::Delay(10),
+                    MockEvent::Reset(true),
+                    MockEvent::Delay(200),
+                ],
+                "{}",
+                panel.name
+            );
+
+            let mut commands = Vec::new();
+            for step in panel.init_sequence {
+                if let InitStep::Command(command, data) = *step {
+                    commands.push(command);
+                    let sent = mock.data_for(command);
+                    assert!(sent.contains(&data.to_vec()), "{} {:#04x}", panel.name, command);
+                }
+            }
+            if panel.custom_luts {
+                commands.extend([
+                    LUT_FOR_VCOM,
+                    LUT_WHITE_TO_WHITE,
+                    LUT_BLACK_TO_WHITE,
+                    LUT_WHITE_TO_BLACK,
+                    LUT_BLACK_TO_BLACK,
+                ]);
+            }
+            assert_eq!(mock.commands(), commands, "{}", panel.name);
+            assert_eq!(epd.power_state(), PowerState::Awake);
+        }
+    }
+
+    #[test]
+    fn init_loads_the_full_waveform_luts() {
+        let mut epd = Epd4in2::with_panel(mock_for(&EPD_4IN2), &EPD_4IN2);
+        epd.init().unwrap();
+
+        let lut = Waveform::Full.lut();
+        let mock = epd.interface();
+        assert_eq!(mock.data_for(LUT_FOR_VCOM), [lut.vcom.to_vec()]);
+        assert_eq!(mock.data_for(LUT_BLACK_TO_BLACK), [lut.bb.to_vec()]);
+    }
+
+    #[test]
+    fn sleep_powers_off_before_deep_sleep() {
+        for panel in PANELS.iter().copied() {
+            let mut epd = awake(panel);
+            epd.sleep().unwrap();
+            let mock = epd.interface();
+
+            match panel.controller {
+                Controller::Uc81xx => {
+                    assert_eq!(mock.commands(), [POWER_OFF, DEEP_SLEEP], "{}", panel.name);
+                    assert_eq!(mock.data_for(DEEP_SLEEP), [vec![0xA5]]);
+                }
+                Controller::Ssd16xx => {
+                    assert_eq!(
+                        mock.commands(),
+                        [SSD_DISPLAY_UPDATE_CONTROL_2, SSD_MASTER_ACTIVATION, SSD_DEEP_SLEEP],
+                        "{}",
+                        panel.name
+                    );
+                    assert_eq!(
+                        mock.data_for(SSD_DISPLAY_UPDATE_CONTROL_2),
+                        [vec![SSD_UPDATE_POWER_OFF]]
+                    );
+                    assert_eq!(mock.data_for(SSD_DEEP_SLEEP), [vec![0x01]]);
+                }
+            }
+            assert_eq!(epd.power_state(), PowerState::DeepSleep);
+        }
+    }
+
+    #[test]
+    fn busy_wait_times_out() {
+        let mut epd = awake(&EPD_7IN5_V2);
+        epd.set_busy_timeout(Duration::from_millis(300));
+        epd.interface_mut().hold_busy(100);
+
+        let error = epd.display_frame(&vec![0xFF; epd.frame_size()]).unwrap_err();
+        assert!(matches!(error, EpdError::BusyTimeout(waited) if waited == Duration::from_millis(300)));
+        assert_eq!(epd.interface().total_delay_ms(), 300);
+    }
+
+    #[test]
+    fn trace_keeps_a_refresh_that_never_finishes() {
+        let path = std::env::temp_dir().join("epaper_ui_epd_trace_test.trace");
+        let mut epd = awake(&EPD_7IN5_V2);
+        epd.start_trace(&path).unwrap();
+        epd.set_busy_timeout(Duration::from_millis(300));
+        epd.interface_mut().hold_busy(100);
+        assert!(epd.display_frame(&vec![0xFF; epd.frame_size()]).is_err());
+
+        // Still tracing, so only what was flushed is in the file
+        let records: Vec<TraceRecord> = crate::display::trace::TraceReader::open(&path)
+            .unwrap()
+            .map(|record| record.unwrap())
+            .collect();
+        assert!(records.contains(&TraceRecord::Command(DISPLAY_REFRESH)));
+        assert_eq!(records.last(), Some(&TraceRecord::Wait(Duration::from_millis(300))));
+        epd.stop_trace().unwrap();
+    }
+
+    #[test]
+    fn busy_wait_returns_once_the_line_is_idle() {
+        let mut epd = awake(&EPD_2IN13_V2);
+        epd.interface_mut().hold_busy(3);
+        epd.wait_until_idle().unwrap();
+        assert_eq!(epd.interface().total_delay_ms(), 3 * BUSY_POLL_INTERVAL_MS);
+    }
+
+    #[test]
+    fn frames_need_an_initialized_panel() {
+        let mut epd = Epd4in2::with_panel(mock_for(&EPD_7IN5_V2), &EPD_7IN5_V2);
+        assert_eq!(epd.power_state(), PowerState::Uninitialized);
+
+        let frame = vec![0xFF; epd.frame_size()];
+        assert!(matches!(
+            epd.display_frame(&frame),
+            Err(EpdError::InvalidState {
+                state: PowerState::Uninitialized,
+                ..
+            })
+        ));
+        assert!(matches!(epd.sleep(), Err(EpdError::InvalidState { .. })));
+        assert!(epd.interface().events().is_empty());
+    }
+
+    #[test]
+    fn frame_after_power_off_powers_on_first() {
+        let mut epd = awake(&EPD_7IN5_V2);
+        epd.power_off().unwrap();
+        assert_eq!(epd.power_state(), PowerState::PoweredOff);
+        epd.interface_mut().clear();
+
+        epd.display_frame(&vec![0xFF; epd.frame_size()]).unwrap();
+        assert_eq!(epd.interface().commands()[0], POWER_ON);
+        assert_eq!(epd.power_state(), PowerState::Awake);
+    }
+
+    #[test]
+    fn frame_after_deep_sleep_reinitializes() {
+        let mut epd = awake(&EPD_7IN5_V2);
+        epd.sleep().unwrap();
+        assert!(matches!(epd.power_off(), Err(EpdError::InvalidState { .. })));
+        epd.interface_mut().clear();
+
+        epd.display_frame(&vec![0xFF; epd.frame_size()]).unwrap();
+        let mock = epd.interface();
+        assert!(mock.events().contains(&MockEvent::Reset(false)));
+        assert_eq!(mock.commands().last(), Some(&DISPLAY_REFRESH));
+        assert_eq!(epd.power_state(), PowerState::Awake);
+
+        // Sleeping twice is harmless
+        epd.sleep().unwrap();
+        epd.sleep().unwrap();
+        assert_eq!(epd.power_state(), PowerState::DeepSleep);
+    }
+
+    #[test]
+    fn frame_of_the_wrong_size_is_rejected() {
+        let mut epd = awake(&EPD_7IN5_V2);
+        assert!(matches!(
+            epd.display_frame(&[0xFF; 10]),
+            Err(EpdError::FrameSizeMismatch { actual: 10, .. })
+        ));
+        assert!(epd.interface().events().is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+use crate::display::epd4in2::PowerState;
+use crate::display::lut::Waveform;
+use crate::ui::Rect;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Errors reported by the display drivers
+#[derive(Debug, Error)]
+pub enum EpdError {
+    /// The BUSY line did not report idle within the configured timeout
+    #[error("display still busy after {0:?}")]
+    BusyTimeout(Duration),
+
+    /// A frame buffer does not match the size of the panel or window
+    #[error("frame buffer is {actual} bytes, expected {expected}")]
+    FrameSizeMismatch { expected: usize, actual: usize },
+
+    /// A partial refresh window cannot be used on this panel
+    #[error("invalid partial window {rect:?}: {reason}")]
+    InvalidWindow { rect: Rect, reason: &'static str },
+
+    /// The operation needs a waveform that is not loaded
+    #[error("{required:?} waveform required, {loaded:?} is loaded")]
+    WaveformMismatch { required: Waveform, loaded: Waveform },
+
+    /// The operation is not valid in the current power state
+    #[error("cannot {operation} while the display is {state:?}")]
+    InvalidState {
+        operation: &'static str,
+        state: PowerState,
+    },
+
+    /// The refresh policy asks for a full refresh but the panel contents are
+    /// unknown, so the partial refresh cannot be turned into one
+    #[error("full refresh required ({0}), send a full frame first")]
+    FullRefreshRequired(&'static str),
+
+    /// The display does not implement the requested operation
+    #[error("{0} is not supported by this display")]
+    Unsupported(&'static str),
+
+    /// No panel model has the given name
+    #[error("unknown panel: {0}")]
+    UnknownPanel(String),
+
+    /// Two panels, or two lines of one panel, share a pin or chip select
+    #[error("conflicting panel configuration: {0}")]
+    ConfigConflict(String),
+
+    /// The controller reported a temperature no panel works at, e.g. because
+    /// the data line cannot be read
+    #[error("implausible temperature reading of {0}°C")]
+    ImplausibleTemperature(f32),
+
+    /// The display worker thread is no longer running
+    #[error("display worker has stopped")]
+    WorkerStopped,
+
+    /// An image file is malformed or uses an unsupported format
+    #[error("cannot decode image: {0}")]
+    ImageDecode(String),
+
+    /// Error from the SPI bus
+    #[error("SPI error: {0}")]
+    Spi(embedded_hal::spi::ErrorKind),
+
+    /// Error from a GPIO pin
+    #[error("GPIO error: {0}")]
+    Gpio(embedded_hal::digital::ErrorKind),
+
+    /// Error from the Raspberry Pi SPI driver
+    #[cfg(feature = "rppal")]
+    #[error("SPI error: {0}")]
+    RppalSpi(#[from] rppal::spi::Error),
+
+    /// Error from the Raspberry Pi GPIO driver
+    #[cfg(feature = "rppal")]
+    #[error("GPIO error: {0}")]
+    RppalGpio(#[from] rppal::gpio::Error),
+
+    /// Error writing simulated output
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl EpdError {
+    /// Whether resetting and re-initializing the panel may clear the error
+    ///
+    /// Bus, pin and busy timeout errors are; errors caused by the arguments
+    /// or the configuration are not.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            EpdError::BusyTimeout(_) | EpdError::Spi(_) | EpdError::Gpio(_) | EpdError::Io(_) => true,
+            #[cfg(feature = "rppal")]
+            EpdError::RppalSpi(_) | EpdError::RppalGpio(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Result type used by the display drivers
+pub type Result<T, E = EpdError> = std::result::Result<T, E>;
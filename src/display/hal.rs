@@ -0,0 +1,102 @@
+use crate::display::error::{EpdError, Result};
+use crate::display::interface::EpdInterface;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, InputPin, OutputPin, PinState};
+use embedded_hal::spi::{self, SpiDevice};
+use std::thread;
+use std::time::Duration;
+
+/// Panel wiring built from `embedded-hal` 1.0 drivers
+///
+/// Works with any SPI device and GPIO implementation, e.g. Linux
+/// spidev/gpio-cdev, a microcontroller HAL or `embedded-hal-mock`. The SPI
+/// device owns the chip select line; to drive several panels from one bus,
+/// give each a device from a bus sharing wrapper such as the ones in
+/// `embedded-hal-bus`.
+pub struct HalInterface<SPI, DC, RST, BUSY, DELAY> {
+    spi: SPI,
+    dc: DC,
+    reset: RST,
+    busy: BUSY,
+    delay: DELAY,
+}
+
+impl<SPI, DC, RST, BUSY, DELAY> HalInterface<SPI, DC, RST, BUSY, DELAY>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    RST: OutputPin,
+    BUSY: InputPin,
+    DELAY: DelayNs,
+{
+    pub fn new(spi: SPI, dc: DC, reset: RST, busy: BUSY, delay: DELAY) -> Self {
+        Self {
+            spi,
+            dc,
+            reset,
+            busy,
+            delay,
+        }
+    }
+
+    /// Give back the SPI device, pins and delay
+    pub fn release(self) -> (SPI, DC, RST, BUSY, DELAY) {
+        (self.spi, self.dc, self.reset, self.busy, self.delay)
+    }
+}
+
+// Keep only the portable error kind of a HAL error
+fn spi_error<E: spi::Error>(error: E) -> EpdError {
+    EpdError::Spi(error.kind())
+}
+
+fn gpio_error<E: digital::Error>(error: E) -> EpdError {
+    EpdError::Gpio(error.kind())
+}
+
+impl<SPI, DC, RST, BUSY, DELAY> EpdInterface for HalInterface<SPI, DC, RST, BUSY, DELAY>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    RST: OutputPin,
+    BUSY: InputPin,
+    DELAY: DelayNs,
+{
+    fn spi_write(&mut self, data: &[u8]) -> Result<()> {
+        self.spi.write(data).map_err(spi_error)
+    }
+
+    fn spi_read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        self.spi.read(buffer).map_err(spi_error)
+    }
+
+    fn set_dc(&mut self, high: bool) -> Result<()> {
+        self.dc.set_state(PinState::from(high)).map_err(gpio_error)
+    }
+
+    fn set_reset(&mut self, high: bool) -> Result<()> {
+        self.reset.set_state(PinState::from(high)).map_err(gpio_error)
+    }
+
+    fn read_busy(&mut self) -> Result<bool> {
+        self.busy.is_high().map_err(gpio_error)
+    }
+
+    fn delay_ms(&mut self, ms: u64) {
+        self.delay.delay_ms(u32::try_from(ms).unwrap_or(u32::MAX));
+    }
+}
+
+/// `DelayNs` implementation that sleeps the current thread
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdDelay;
+
+impl DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        thread::sleep(Duration::from_nanos(ns.into()));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        thread::sleep(Duration::from_millis(ms.into()));
+    }
+}
@@ -0,0 +1,380 @@
+use crate::display::bitmap::decode_gray;
+use crate::display::driver::check_buffer_size;
+use crate::display::error::Result;
+use std::fs;
+use std::path::Path;
+
+/// How gray values are reduced to the levels of the frame buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Plain threshold, pixels at or above the value become white on a
+    /// 1-bit buffer (4-level buffers round to the nearest level)
+    Threshold(u8),
+    /// Ordered dithering with a 4x4 Bayer matrix, stable between frames
+    Bayer,
+    /// Error diffusion to four neighbours, the smoothest gradients
+    #[default]
+    FloydSteinberg,
+    /// Error diffusion of 3/4 of the error, crisper with more contrast
+    Atkinson,
+}
+
+// Threshold map for ordered dithering
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Tone adjustment and dithering used by `Paint::draw_image`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageStyle {
+    pub dither: Dither,
+    /// Added to every value, -1.0 (black) to 1.0 (white)
+    pub brightness: f32,
+    /// Factor applied around mid gray, 1.0 keeps the contrast
+    pub contrast: f32,
+    /// Values above 1.0 lighten the mid tones, below 1.0 darken them
+    pub gamma: f32,
+}
+
+impl Default for ImageStyle {
+    fn default() -> Self {
+        Self {
+            dither: Dither::default(),
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl ImageStyle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub fn brightness(mut self, brightness: f32) -> Self {
+        self.brightness = brightness.clamp(-1.0, 1.0);
+        self
+    }
+
+    /// Negative values are treated as 0.0 (flat gray)
+    pub fn contrast(mut self, contrast: f32) -> Self {
+        self.contrast = contrast.max(0.0);
+        self
+    }
+
+    /// Values at or below 0.0 are ignored
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        if gamma > 0.0 {
+            self.gamma = gamma;
+        }
+        self
+    }
+
+    // Lookup table mapping source values to adjusted values (0.0-1.0)
+    fn tone_curve(&self) -> [f32; 256] {
+        let mut curve = [0.0; 256];
+        for (value, entry) in curve.iter_mut().enumerate() {
+            let v = value as f32 / 255.0;
+            let v = ((v - 0.5) * self.contrast + 0.5 + self.brightness).clamp(0.0, 1.0);
+            *entry = v.powf(1.0 / self.gamma);
+        }
+        curve
+    }
+}
+
+// Luma of an 8-bit RGB color (ITU-R BT.601 weights)
+pub(crate) fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+/// An 8-bit grayscale image, 0 is black and 255 is white
+///
+/// Convert RGB sources with `from_rgb`, then draw the image with
+/// `Paint::draw_image`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrayImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl GrayImage {
+    /// Create a white image
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0xFF; width as usize * height as usize],
+        }
+    }
+
+    /// Wrap one byte per pixel, row by row
+    pub fn from_luma(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self> {
+        check_buffer_size(&pixels, width as usize * height as usize)?;
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Convert 8-bit RGB triplets, row by row, to their luma
+    pub fn from_rgb(width: u32, height: u32, rgb: &[u8]) -> Result<Self> {
+        check_buffer_size(rgb, width as usize * height as usize * 3)?;
+        let pixels = rgb.chunks_exact(3).map(|p| luma(p[0], p[1], p[2])).collect();
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Decode a PBM/PGM/PPM, BMP or PNG image from memory
+    ///
+    /// Color images are converted to their luma and transparent pixels are
+    /// blended onto white.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        decode_gray(bytes)
+    }
+
+    /// Load an image file, see `decode`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Value of a pixel, `None` outside the image
+    pub fn get(&self, x: u32, y: u32) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.pixels[(y * self.width + x) as usize])
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, value: u8) {
+        if x < self.width && y < self.height {
+            self.pixels[(y * self.width + x) as usize] = value;
+        }
+    }
+
+    /// Resample to the given size
+    ///
+    /// Each target pixel averages the source pixels it covers, so shrinking
+    /// a photo does not alias; enlarging repeats pixels.
+    pub fn scaled(&self, width: u32, height: u32) -> GrayImage {
+        let mut scaled = GrayImage::new(width, height);
+        if self.width == 0 || self.height == 0 {
+            return scaled;
+        }
+
+        // Source range covered by target index `i` along an axis
+        fn span(i: u32, target: u32, source: u32) -> (u32, u32) {
+            let start = (i as u64 * source as u64 / target as u64) as u32;
+            let end = ((i as u64 + 1) * source as u64).div_ceil(target as u64) as u32;
+            (start, end.max(start + 1).min(source))
+        }
+
+        for y in 0..height {
+            let (y0, y1) = span(y, height, self.height);
+            for x in 0..width {
+                let (x0, x1) = span(x, width, self.width);
+                let mut sum = 0u64;
+                for sy in y0..y1 {
+                    let row = sy as usize * self.width as usize;
+                    sum += self.pixels[row + x0 as usize..row + x1 as usize]
+                        .iter()
+                        .map(|&v| v as u64)
+                        .sum::<u64>();
+                }
+                let count = (x1 - x0) as u64 * (y1 - y0) as u64;
+                scaled.pixels[(y * width + x) as usize] = ((sum + count / 2) / count) as u8;
+            }
+        }
+        scaled
+    }
+
+    /// Reduce the image to `levels` gray levels (2 for black/white, 4 for
+    /// grayscale buffers)
+    ///
+    /// Returns one level per pixel, 0 being black and `levels - 1` white.
+    pub fn quantize(&self, levels: u8, style: &ImageStyle) -> Vec<u8> {
+        let steps = (levels.max(2) - 1) as f32;
+        let curve = style.tone_curve();
+        let mut values: Vec<f32> = self.pixels.iter().map(|&v| curve[v as usize]).collect();
+        let mut output = vec![0u8; values.len()];
+
+        let nearest = |v: f32| (v.clamp(0.0, 1.0) * steps).round();
+        let (width, height) = (self.width as usize, self.height as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let index = y * width + x;
+                let value = values[index];
+                let level = match style.dither {
+                    Dither::Threshold(threshold) => {
+                        let threshold = threshold as f32 / 255.0;
+                        if steps == 1.0 {
+                            (value >= threshold) as u8 as f32
+                        } else {
+                            nearest(value)
+                        }
+                    }
+                    Dither::Bayer => {
+                        let offset = (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5;
+                        nearest(value + offset / steps)
+                    }
+                    Dither::FloydSteinberg | Dither::Atkinson => nearest(value),
+                };
+                output[index] = level as u8;
+
+                let error = value - level / steps;
+                let neighbours: &[(isize, usize, f32)] = match style.dither {
+                    Dither::FloydSteinberg => &[
+                        (1, 0, 7.0 / 16.0),
+                        (-1, 1, 3.0 / 16.0),
+                        (0, 1, 5.0 / 16.0),
+                        (1, 1, 1.0 / 16.0),
+                    ],
+                    Dither::Atkinson => &[
+                        (1, 0, 1.0 / 8.0),
+                        (2, 0, 1.0 / 8.0),
+                        (-1, 1, 1.0 / 8.0),
+                        (0, 1, 1.0 / 8.0),
+                        (1, 1, 1.0 / 8.0),
+                        (0, 2, 1.0 / 8.0),
+                    ],
+                    _ => &[],
+                };
+                for &(dx, dy, weight) in neighbours {
+                    let nx = x as isize + dx;
+                    let ny = y + dy;
+                    if nx >= 0 && (nx as usize) < width && ny < height {
+                        values[ny * width + nx as usize] += error * weight;
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform(width: u32, height: u32, value: u8) -> GrayImage {
+        GrayImage::from_luma(width, height, vec![value; (width * height) as usize]).unwrap()
+    }
+
+    fn mean(levels: &[u8]) -> f32 {
+        levels.iter().map(|&level| level as f32).sum::<f32>() / levels.len() as f32
+    }
+
+    #[test]
+    fn threshold_splits_at_the_value_or_rounds_to_the_nearest_level() {
+        let image = GrayImage::from_luma(4, 1, vec![0, 100, 160, 255]).unwrap();
+        let style = ImageStyle::new().dither(Dither::Threshold(128));
+        assert_eq!(image.quantize(2, &style), [0, 0, 1, 1]);
+        assert_eq!(image.quantize(4, &style), [0, 1, 2, 3]);
+
+        let low = ImageStyle::new().dither(Dither::Threshold(90));
+        assert_eq!(image.quantize(2, &low), [0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn bayer_follows_the_threshold_map() {
+        let image = uniform(4, 4, 128);
+        let style = ImageStyle::new().dither(Dither::Bayer);
+        let two = image.quantize(2, &style);
+        let four = image.quantize(4, &style);
+        for y in 0..4 {
+            for x in 0..4 {
+                let upper = (BAYER_4X4[y][x] >= 8) as u8;
+                assert_eq!(two[y * 4 + x], upper, "{} {}", x, y);
+                assert_eq!(four[y * 4 + x], 1 + upper, "{} {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn floyd_steinberg_diffuses_the_error() {
+        let style = ImageStyle::new().dither(Dither::FloydSteinberg);
+        assert_eq!(uniform(4, 1, 128).quantize(2, &style), [1, 0, 1, 0]);
+
+        // 40% gray keeps its average with the two nearest levels
+        let image = uniform(16, 16, 102);
+        let two = image.quantize(2, &style);
+        assert!((mean(&two) - 0.4).abs() < 0.02, "{}", mean(&two));
+        let four = image.quantize(4, &style);
+        assert!(four.iter().all(|&level| level == 1 || level == 2));
+        assert!((mean(&four) - 1.2).abs() < 0.05, "{}", mean(&four));
+    }
+
+    #[test]
+    fn atkinson_diffuses_three_quarters_of_the_error() {
+        let style = ImageStyle::new().dither(Dither::Atkinson);
+        assert_eq!(uniform(4, 1, 128).quantize(2, &style), [1, 0, 0, 1]);
+
+        // Dropping a quarter of the error pushes 40% gray towards black
+        let image = uniform(16, 16, 102);
+        let two = image.quantize(2, &style);
+        assert!(mean(&two) > 0.25 && mean(&two) < 0.4, "{}", mean(&two));
+        let four = image.quantize(4, &style);
+        assert!(four.iter().all(|&level| level == 1 || level == 2));
+        assert!(four.contains(&2));
+    }
+
+    #[test]
+    fn tone_curve_applies_brightness_contrast_and_gamma() {
+        let identity = ImageStyle::new().tone_curve();
+        assert!((0..256).all(|v| (identity[v] - v as f32 / 255.0).abs() < 1e-6));
+
+        let brighter = ImageStyle::new().brightness(0.5).tone_curve();
+        assert!((brighter[0] - 0.5).abs() < 1e-6);
+        assert_eq!(brighter[200], 1.0);
+
+        let contrast = ImageStyle::new().contrast(2.0).tone_curve();
+        assert!(contrast[64] < 0.01 && contrast[191] > 0.99);
+        assert!(contrast[0] == 0.0 && contrast[255] == 1.0);
+        let flat = ImageStyle::new().contrast(-1.0).tone_curve();
+        assert!(flat.iter().all(|&v| (v - 0.5).abs() < 1e-6));
+
+        let lighter = ImageStyle::new().gamma(2.0).tone_curve();
+        assert!((lighter[64] - (64.0f32 / 255.0).sqrt()).abs() < 1e-6);
+        assert_eq!(ImageStyle::new().gamma(0.0).gamma, 1.0);
+        assert_eq!(ImageStyle::new().brightness(3.0).brightness, 1.0);
+    }
+
+    #[test]
+    fn scaled_averages_when_shrinking_and_repeats_when_enlarging() {
+        let image = GrayImage::from_luma(4, 2, vec![0, 100, 200, 255, 50, 50, 50, 50]).unwrap();
+        assert_eq!(image.scaled(2, 1).pixels(), [50, 139]);
+
+        // Uneven ratios share the source pixel on the boundary
+        let row = GrayImage::from_luma(3, 1, vec![0, 90, 180]).unwrap();
+        assert_eq!(row.scaled(2, 1).pixels(), [45, 135]);
+
+        let small = GrayImage::from_luma(2, 1, vec![10, 20]).unwrap();
+        assert_eq!(small.scaled(4, 2).pixels(), [10, 10, 20, 20, 10, 10, 20, 20]);
+    }
+
+    #[test]
+    fn scaled_large_image_into_one_pixel() {
+        assert_eq!(uniform(5000, 4000, 255).scaled(1, 1).pixels(), [255]);
+    }
+}
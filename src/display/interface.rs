@@ -0,0 +1,115 @@
+#[cfg(feature = "rppal")]
+use crate::display::config::EpdConfig;
+use crate::display::error::{EpdError, Result};
+#[cfg(feature = "rppal")]
+use rppal::gpio::{Gpio, InputPin, Level, OutputPin};
+#[cfg(feature = "rppal")]
+use rppal::spi::{Mode, Spi};
+#[cfg(feature = "rppal")]
+use std::thread;
+#[cfg(feature = "rppal")]
+use std::time::Duration;
+
+/// Low-level connection between the panel driver and the controller
+///
+/// Pin levels are plain booleans where `true` means high.
+pub trait EpdInterface {
+    /// Write raw bytes over SPI
+    fn spi_write(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Read raw bytes over SPI
+    ///
+    /// Most HATs only wire the controller's data input, in which case
+    /// nothing can be read back.
+    fn spi_read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        let _ = buffer;
+        Err(EpdError::Unsupported("reading from the controller"))
+    }
+
+    /// Drive the data/command select line (low selects command mode)
+    fn set_dc(&mut self, high: bool) -> Result<()>;
+
+    /// Drive the reset line (active low)
+    fn set_reset(&mut self, high: bool) -> Result<()>;
+
+    /// Read the level of the BUSY line
+    ///
+    /// Which level means busy depends on the controller, see
+    /// `Controller::busy_level`.
+    fn read_busy(&mut self) -> Result<bool>;
+
+    /// Block for the given number of milliseconds
+    fn delay_ms(&mut self, ms: u64);
+}
+
+/// Raspberry Pi wiring of the panel using rppal
+#[cfg(feature = "rppal")]
+pub struct RppalInterface {
+    spi: Spi,
+    reset_pin: OutputPin,
+    dc_pin: OutputPin,
+    busy_pin: InputPin,
+    miso_wired: bool,
+}
+
+#[cfg(feature = "rppal")]
+impl RppalInterface {
+    pub fn new(config: &EpdConfig) -> Result<Self> {
+        // Initialize SPI with correct settings
+        let spi = Spi::new(
+            config.spi_bus,
+            config.slave_select,
+            config.spi_speed_hz,
+            Mode::Mode0, // SPI mode 0
+        )?;
+
+        // Initialize GPIO pins
+        let gpio = Gpio::new()?;
+        let reset_pin = gpio.get(config.rst_pin)?.into_output();
+        let dc_pin = gpio.get(config.dc_pin)?.into_output();
+        let busy_pin = gpio.get(config.busy_pin)?.into_input();
+
+        Ok(Self {
+            spi,
+            reset_pin,
+            dc_pin,
+            busy_pin,
+            miso_wired: config.miso_wired,
+        })
+    }
+}
+
+#[cfg(feature = "rppal")]
+impl EpdInterface for RppalInterface {
+    fn spi_write(&mut self, data: &[u8]) -> Result<()> {
+        self.spi.write(data)?;
+        Ok(())
+    }
+
+    // A floating MISO line would read as made-up data
+    fn spi_read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        if !self.miso_wired {
+            return Err(EpdError::Unsupported("reading from the controller"));
+        }
+        self.spi.read(buffer)?;
+        Ok(())
+    }
+
+    fn set_dc(&mut self, high: bool) -> Result<()> {
+        self.dc_pin.write(Level::from(high));
+        Ok(())
+    }
+
+    fn set_reset(&mut self, high: bool) -> Result<()> {
+        self.reset_pin.write(Level::from(high));
+        Ok(())
+    }
+
+    fn read_busy(&mut self) -> Result<bool> {
+        Ok(self.busy_pin.read() == Level::High)
+    }
+
+    fn delay_ms(&mut self, ms: u64) {
+        thread::sleep(Duration::from_millis(ms));
+    }
+}
@@ -0,0 +1,240 @@
+/// Length of the VCOM lookup table in bytes
+pub const LUT_VCOM_LEN: usize = 44;
+/// Length of each pixel transition lookup table in bytes
+pub const LUT_LEN: usize = 42;
+
+/// Waveform used when refreshing the panel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Waveform {
+    /// Slow refresh with full contrast and no ghosting
+    #[default]
+    Full,
+    /// Refresh in well under a second at the cost of some contrast
+    Fast,
+    /// Four gray levels, driven from two data planes
+    Gray4,
+}
+
+impl Waveform {
+    /// The lookup tables implementing this waveform
+    pub fn lut(&self) -> &'static LutSet {
+        match self {
+            Waveform::Full => &LUT_FULL,
+            Waveform::Fast => &LUT_FAST,
+            Waveform::Gray4 => &LUT_GRAY4,
+        }
+    }
+}
+
+/// Lookup tables loaded into the controller registers
+///
+/// Each table is a list of 6 byte phases: a level select byte followed by
+/// four frame counts and a repeat count. The VCOM table has two extra
+/// trailing bytes. Unused phases are zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LutSet {
+    /// VCOM voltage levels
+    pub vcom: [u8; LUT_VCOM_LEN],
+    /// White to white transition
+    pub ww: [u8; LUT_LEN],
+    /// Black to white transition
+    pub bw: [u8; LUT_LEN],
+    /// White to black transition
+    pub wb: [u8; LUT_LEN],
+    /// Black to black transition
+    pub bb: [u8; LUT_LEN],
+}
+
+impl LutSet {
+    /// Copy of the tables with every frame count multiplied by `factor`
+    ///
+    /// Non-zero counts stay within 1..=255 so no phase is dropped.
+    pub fn scaled(&self, factor: f32) -> LutSet {
+        let mut lut = self.clone();
+        for table in [
+            &mut lut.vcom[..],
+            &mut lut.ww[..],
+            &mut lut.bw[..],
+            &mut lut.wb[..],
+            &mut lut.bb[..],
+        ] {
+            for phase in table.chunks_exact_mut(6) {
+                for frames in &mut phase[1..5] {
+                    if *frames != 0 {
+                        *frames = (*frames as f32 * factor).round().clamp(1.0, 255.0) as u8;
+                    }
+                }
+            }
+        }
+        lut
+    }
+
+    /// Copy of the tables adjusted for the given ambient temperature in °C
+    pub fn for_temperature(&self, celsius: f32) -> LutSet {
+        self.scaled(temperature_factor(celsius))
+    }
+}
+
+/// Frame count multiplier for an ambient temperature in °C
+///
+/// The particles move slower in the cold, so the tables (tuned for room
+/// temperature) need longer phases below ~18°C and shorter ones when hot.
+pub fn temperature_factor(celsius: f32) -> f32 {
+    match celsius {
+        t if t < 0.0 => 2.0,
+        t if t < 10.0 => 1.5,
+        t if t < 18.0 => 1.2,
+        t if t < 30.0 => 1.0,
+        _ => 0.8,
+    }
+}
+
+/// Full quality waveform from the Waveshare reference driver
+pub const LUT_FULL: LutSet = LutSet {
+    vcom: [
+        0x00, 0x17, 0x00, 0x00, 0x00, 0x02,
+        0x00, 0x17, 0x17, 0x00, 0x00, 0x02,
+        0x00, 0x0A, 0x01, 0x00, 0x00, 0x01,
+        0x00, 0x0E, 0x0E, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ],
+    ww: [
+        0x40, 0x17, 0x00, 0x00, 0x00, 0x02,
+        0x90, 0x17, 0x17, 0x00, 0x00, 0x02,
+        0x40, 0x0A, 0x01, 0x00, 0x00, 0x01,
+        0xA0, 0x0E, 0x0E, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    bw: [
+        0x40, 0x17, 0x00, 0x00, 0x00, 0x02,
+        0x90, 0x17, 0x17, 0x00, 0x00, 0x02,
+        0x40, 0x0A, 0x01, 0x00, 0x00, 0x01,
+        0xA0, 0x0E, 0x0E, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    wb: [
+        0x80, 0x17, 0x00, 0x00, 0x00, 0x02,
+        0x90, 0x17, 0x17, 0x00, 0x00, 0x02,
+        0x80, 0x0A, 0x01, 0x00, 0x00, 0x01,
+        0x50, 0x0E, 0x0E, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    bb: [
+        0x80, 0x17, 0x00, 0x00, 0x00, 0x02,
+        0x90, 0x17, 0x17, 0x00, 0x00, 0x02,
+        0x80, 0x0A, 0x01, 0x00, 0x00, 0x01,
+        0x50, 0x0E, 0x0E, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+};
+
+/// Two phase waveform refreshing in roughly 300ms
+pub const LUT_FAST: LutSet = LutSet {
+    vcom: [
+        0x00, 0x0E, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x0E, 0x0E, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ],
+    ww: [
+        0x20, 0x0E, 0x00, 0x00, 0x00, 0x01,
+        0x90, 0x0E, 0x0E, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    bw: [
+        0x20, 0x0E, 0x00, 0x00, 0x00, 0x01,
+        0x90, 0x0E, 0x0E, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    wb: [
+        0x10, 0x0E, 0x00, 0x00, 0x00, 0x01,
+        0x60, 0x0E, 0x0E, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    bb: [
+        0x10, 0x0E, 0x00, 0x00, 0x00, 0x01,
+        0x60, 0x0E, 0x0E, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+};
+
+/// Four level grayscale waveform; the old/new data planes select the level
+pub const LUT_GRAY4: LutSet = LutSet {
+    vcom: [
+        0x00, 0x0A, 0x00, 0x00, 0x00, 0x01,
+        0x60, 0x14, 0x14, 0x00, 0x00, 0x01,
+        0x00, 0x14, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x13, 0x0A, 0x01, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ],
+    ww: [
+        0x40, 0x0A, 0x00, 0x00, 0x00, 0x01,
+        0x90, 0x14, 0x14, 0x00, 0x00, 0x01,
+        0x10, 0x14, 0x0A, 0x00, 0x00, 0x01,
+        0xA0, 0x13, 0x01, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    bw: [
+        0x40, 0x0A, 0x00, 0x00, 0x00, 0x01,
+        0x90, 0x14, 0x14, 0x00, 0x00, 0x01,
+        0x00, 0x14, 0x0A, 0x00, 0x00, 0x01,
+        0x99, 0x0C, 0x01, 0x03, 0x04, 0x01,
+        0x02, 0x04, 0x01, 0x03, 0x04, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    wb: [
+        0x40, 0x0A, 0x00, 0x00, 0x00, 0x01,
+        0x90, 0x14, 0x14, 0x00, 0x00, 0x01,
+        0x00, 0x14, 0x0A, 0x00, 0x00, 0x01,
+        0x99, 0x0B, 0x04, 0x04, 0x01, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    bb: [
+        0x80, 0x0A, 0x00, 0x00, 0x00, 0x01,
+        0x90, 0x14, 0x14, 0x00, 0x00, 0x01,
+        0x20, 0x14, 0x0A, 0x00, 0x00, 0x01,
+        0x50, 0x13, 0x01, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+};
@@ -0,0 +1,187 @@
+use crate::display::interface::EpdInterface;
+use crate::display::error::Result;
+use std::collections::VecDeque;
+
+/// A single interaction recorded by `MockInterface`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockEvent {
+    /// Byte written while DC was low
+    Command(u8),
+    /// Bytes written while DC was high
+    Data(Vec<u8>),
+    /// Bytes returned for an SPI read
+    Read(Vec<u8>),
+    /// Transition of the DC line
+    Dc(bool),
+    /// Transition of the reset line
+    Reset(bool),
+    /// Level returned for a read of the BUSY line
+    Busy(bool),
+    /// Requested delay in milliseconds
+    Delay(u64),
+}
+
+/// Interface that records the command stream instead of talking to hardware
+///
+/// BUSY reads are answered from a script; once the script runs out the
+/// line reports the idle level (high unless changed with `set_idle_level`).
+/// SPI reads are answered from `script_read`, then with zeros. Delays are
+/// recorded but never slept.
+#[derive(Debug)]
+pub struct MockInterface {
+    events: Vec<MockEvent>,
+    busy_script: VecDeque<bool>,
+    read_script: VecDeque<u8>,
+    idle_level: bool,
+    dc: Option<bool>,
+    reset: Option<bool>,
+}
+
+impl Default for MockInterface {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+            busy_script: VecDeque::new(),
+            read_script: VecDeque::new(),
+            idle_level: true,
+            dc: None,
+            reset: None,
+        }
+    }
+}
+
+impl MockInterface {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Level reported on BUSY once the script runs out
+    ///
+    /// UC81xx controllers idle high, SSD16xx controllers idle low.
+    pub fn set_idle_level(&mut self, high: bool) {
+        self.idle_level = high;
+    }
+
+    /// Queue levels to be returned by the next BUSY reads
+    pub fn script_busy<T: IntoIterator<Item = bool>>(&mut self, levels: T) {
+        self.busy_script.extend(levels);
+    }
+
+    /// Report busy for the next `reads` reads
+    pub fn hold_busy(&mut self, reads: usize) {
+        self.script_busy(std::iter::repeat_n(!self.idle_level, reads));
+    }
+
+    /// Queue bytes to be returned by the next SPI reads
+    pub fn script_read(&mut self, bytes: &[u8]) {
+        self.read_script.extend(bytes);
+    }
+
+    /// All events recorded so far
+    pub fn events(&self) -> &[MockEvent] {
+        &self.events
+    }
+
+    /// Forget the recorded events, keeping the busy script
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Command bytes in the order they were sent
+    pub fn commands(&self) -> Vec<u8> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                MockEvent::Command(command) => Some(*command),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Data sent after each occurrence of `command`, concatenated per occurrence
+    pub fn data_for(&self, command: u8) -> Vec<Vec<u8>> {
+        let mut result = Vec::new();
+        let mut current: Option<Vec<u8>> = None;
+
+        for event in &self.events {
+            match event {
+                MockEvent::Command(c) => {
+                    if let Some(data) = current.take() {
+                        result.push(data);
+                    }
+                    if *c == command {
+                        current = Some(Vec::new());
+                    }
+                }
+                MockEvent::Data(bytes) => {
+                    if let Some(data) = current.as_mut() {
+                        data.extend_from_slice(bytes);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(data) = current {
+            result.push(data);
+        }
+        result
+    }
+
+    /// Total time the driver asked to sleep, in milliseconds
+    pub fn total_delay_ms(&self) -> u64 {
+        self.events
+            .iter()
+            .map(|event| match event {
+                MockEvent::Delay(ms) => *ms,
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+impl EpdInterface for MockInterface {
+    fn spi_write(&mut self, data: &[u8]) -> Result<()> {
+        if self.dc == Some(true) {
+            self.events.push(MockEvent::Data(data.to_vec()));
+        } else {
+            self.events
+                .extend(data.iter().map(|byte| MockEvent::Command(*byte)));
+        }
+        Ok(())
+    }
+
+    fn spi_read(&mut self, buffer: &mut [u8]) -> Result<()> {
+        for byte in buffer.iter_mut() {
+            *byte = self.read_script.pop_front().unwrap_or(0);
+        }
+        self.events.push(MockEvent::Read(buffer.to_vec()));
+        Ok(())
+    }
+
+    fn set_dc(&mut self, high: bool) -> Result<()> {
+        if self.dc != Some(high) {
+            self.dc = Some(high);
+            self.events.push(MockEvent::Dc(high));
+        }
+        Ok(())
+    }
+
+    fn set_reset(&mut self, high: bool) -> Result<()> {
+        if self.reset != Some(high) {
+            self.reset = Some(high);
+            self.events.push(MockEvent::Reset(high));
+        }
+        Ok(())
+    }
+
+    fn read_busy(&mut self) -> Result<bool> {
+        let level = self.busy_script.pop_front().unwrap_or(self.idle_level);
+        self.events.push(MockEvent::Busy(level));
+        Ok(level)
+    }
+
+    fn delay_ms(&mut self, ms: u64) {
+        self.events.push(MockEvent::Delay(ms));
+    }
+}
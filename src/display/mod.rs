@@ -1,5 +1,46 @@
+#[cfg(feature = "async")]
+pub mod async_epd;
+pub mod bitmap;
+pub mod config;
+pub mod driver;
+#[cfg(feature = "rppal")]
+pub mod env;
 pub mod epd4in2;
+pub mod error;
+pub mod hal;
+pub mod image;
+pub mod interface;
+pub mod lut;
+pub mod mock;
 pub mod paint;
+pub mod panel;
+pub mod recovery;
+pub mod refresh;
+pub mod simulated;
+pub mod trace;
+pub mod worker;
 
-pub use epd4in2::{Epd4in2, EPD_WIDTH, EPD_HEIGHT};
-pub use paint::{Paint, ROTATE_0, ROTATE_90, ROTATE_180, ROTATE_270, COLORED, UNCOLORED};
\ No newline at end of file
+#[cfg(feature = "async")]
+pub use async_epd::AsyncEpd;
+pub use bitmap::{Bitmap, BitmapMode};
+pub use config::EpdConfig;
+pub use driver::{DisplayDriver, RefreshCapabilities};
+#[cfg(feature = "rppal")]
+pub use env::{open_display, open_displays};
+pub use epd4in2::{Epd4in2, PowerState, EPD_WIDTH, EPD_HEIGHT};
+pub use error::EpdError;
+pub use hal::{HalInterface, StdDelay};
+pub use image::{Dither, GrayImage, ImageStyle};
+pub use interface::EpdInterface;
+#[cfg(feature = "rppal")]
+pub use interface::RppalInterface;
+pub use lut::{LutSet, Waveform};
+pub use mock::{MockEvent, MockInterface};
+pub use panel::{find_panel, Panel, PANELS};
+pub use recovery::{RecoveringDriver, RetryPolicy};
+pub use refresh::{RefreshManager, RefreshOutcome, RefreshPolicy};
+pub use simulated::SimulatedDisplay;
+pub use trace::{TraceReader, TraceRecord, TraceWriter};
+pub use worker::{DisplayWorker, FrameUpdate, WorkerEvent};
+pub use paint::{ColorPlane, Paint, ROTATE_0, ROTATE_90, ROTATE_180, ROTATE_270, COLORED, UNCOLORED};
+pub use paint::{GRAY_BLACK, GRAY_DARK, GRAY_LIGHT, GRAY_WHITE};
\ No newline at end of file
@@ -1,5 +1,10 @@
-use anyhow::Result;
+use crate::display::bitmap::{Bitmap, BitmapMode};
+use crate::display::image::{GrayImage, ImageStyle};
+use crate::ui::Rect;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Pixel, Size};
 use std::cmp::{max, min};
+use std::convert::Infallible;
 
 // Constants for rotation
 pub const ROTATE_0: u8 = 0;
@@ -11,42 +16,117 @@ pub const ROTATE_270: u8 = 3;
 pub const COLORED: bool = true;
 pub const UNCOLORED: bool = false;
 
+// Constants for 4-level grayscale
+pub const GRAY_BLACK: u8 = 0;
+pub const GRAY_DARK: u8 = 1;
+pub const GRAY_LIGHT: u8 = 2;
+pub const GRAY_WHITE: u8 = 3;
+
+/// Color plane that drawing operations write to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorPlane {
+    /// The black/white image
+    Black,
+    /// The red or yellow image of a tri-color panel
+    Chromatic,
+}
+
 pub struct Paint {
     image: Vec<u8>,
+    chromatic: Option<Vec<u8>>,
+    plane: ColorPlane,
     width: u32,
     height: u32,
     rotate: u8,
+    bits_per_pixel: u8,
+    gray_ink: u8,
+    clip_stack: Vec<Rect>,
 }
 
 impl Paint {
     /// Create a new Paint instance with a buffer of given width and height
     pub fn new(width: u32, height: u32) -> Self {
-        let buffer_size = (width * height / 8) as usize;
+        let buffer_size = width.div_ceil(8) as usize * height as usize;
         let image = vec![0xFF; buffer_size]; // Initialize to white
         
         Self {
             image,
+            chromatic: None,
+            plane: ColorPlane::Black,
+            width,
+            height,
+            rotate: ROTATE_0,
+            bits_per_pixel: 1,
+            gray_ink: GRAY_BLACK,
+            clip_stack: Vec::new(),
+        }
+    }
+
+    /// Create a new 4-level grayscale Paint instance (2 bits per pixel)
+    pub fn new_gray4(width: u32, height: u32) -> Self {
+        // Rows are padded like the 1-bit layout so the bit planes line up
+        let buffer_size = width.div_ceil(8) as usize * 2 * height as usize;
+        let image = vec![0xFF; buffer_size]; // Initialize to white
+
+        Self {
+            image,
+            chromatic: None,
+            plane: ColorPlane::Black,
             width,
             height,
             rotate: ROTATE_0,
+            bits_per_pixel: 2,
+            gray_ink: GRAY_BLACK,
+            clip_stack: Vec::new(),
         }
     }
 
+    /// Create a new Paint instance with a second (red or yellow) color plane
+    ///
+    /// Both planes use the 1-bit layout, a cleared bit marking a colored pixel.
+    pub fn new_tricolor(width: u32, height: u32) -> Self {
+        let mut paint = Self::new(width, height);
+        paint.chromatic = Some(paint.image.clone());
+        paint
+    }
+
     /// Create a Paint instance with an existing buffer
     pub fn with_buffer(buffer: Vec<u8>, width: u32, height: u32) -> Self {
         Self {
             image: buffer,
+            chromatic: None,
+            plane: ColorPlane::Black,
             width,
             height,
             rotate: ROTATE_0,
+            bits_per_pixel: 1,
+            gray_ink: GRAY_BLACK,
+            clip_stack: Vec::new(),
         }
     }
 
     /// Clear the buffer to the specified color
+    ///
+    /// Clearing to UNCOLORED clears every plane, clearing to COLORED only
+    /// fills the current plane.
     pub fn clear(&mut self, colored: bool) {
-        let fill_value = if colored { 0x00 } else { 0xFF };
-        for byte in self.image.iter_mut() {
-            *byte = fill_value;
+        let fill_value = match (colored, self.is_grayscale()) {
+            (true, true) => self.gray_ink * 0x55, // Ink level repeated in every pixel
+            (true, false) => 0x00,
+            (false, _) => 0xFF,
+        };
+
+        if !colored {
+            if let Some(chromatic) = self.chromatic.as_mut() {
+                chromatic.fill(fill_value);
+            }
+            self.image.fill(fill_value);
+            return;
+        }
+
+        match (self.plane, self.chromatic.as_mut()) {
+            (ColorPlane::Chromatic, Some(chromatic)) => chromatic.fill(fill_value),
+            _ => self.image.fill(fill_value),
         }
     }
 
@@ -75,8 +155,121 @@ impl Paint {
         &self.image
     }
 
+    /// Get the red/yellow image of a tri-color Paint
+    pub fn get_color_image(&self) -> Option<&[u8]> {
+        self.chromatic.as_deref()
+    }
+
+    /// Whether the Paint has a second color plane
+    pub fn is_tricolor(&self) -> bool {
+        self.chromatic.is_some()
+    }
+
+    /// Select the plane written by drawing operations
+    ///
+    /// Without a second color plane everything is drawn to the black plane.
+    pub fn set_plane(&mut self, plane: ColorPlane) {
+        self.plane = plane;
+    }
+
+    /// Get the plane written by drawing operations
+    pub fn get_plane(&self) -> ColorPlane {
+        self.plane
+    }
+
+    /// Whether the buffer stores 4 gray levels per pixel
+    pub fn is_grayscale(&self) -> bool {
+        self.bits_per_pixel == 2
+    }
+
+    /// Set the gray level used when drawing with COLORED (grayscale only)
+    pub fn set_gray_ink(&mut self, level: u8) {
+        self.gray_ink = min(level, GRAY_WHITE);
+    }
+
+    /// Get the gray level used when drawing with COLORED
+    pub fn get_gray_ink(&self) -> u8 {
+        self.gray_ink
+    }
+
+    /// Draw a gray pixel at absolute coordinates (ignoring rotation)
+    fn draw_absolute_gray_pixel(&mut self, x: i32, y: i32, level: u8) {
+        if x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32 {
+            return;
+        }
+
+        // Four pixels per byte, leftmost pixel in the top bits
+        let x_pos = x as u32;
+        let y_pos = y as u32;
+        let addr = (x_pos / 4 + y_pos * self.width.div_ceil(8) * 2) as usize;
+
+        if addr >= self.image.len() {
+            return;
+        }
+
+        let shift = 6 - 2 * (x_pos % 4);
+        self.image[addr] &= !(0x03 << shift);
+        self.image[addr] |= (min(level, GRAY_WHITE) & 0x03) << shift;
+    }
+
+    /// Restrict drawing to `rect` (in rotated coordinates) until `pop_clip`
+    ///
+    /// The region is intersected with the current one, so nested regions
+    /// never draw outside their parents. `clear` is not clipped.
+    pub fn push_clip(&mut self, rect: Rect) {
+        let clip = match self.clip_stack.last() {
+            Some(current) => current.intersection(&rect),
+            None => rect,
+        };
+        self.clip_stack.push(clip);
+    }
+
+    /// Restore the clipping region active before the last `push_clip`
+    pub fn pop_clip(&mut self) -> Option<Rect> {
+        self.clip_stack.pop()
+    }
+
+    /// Current clipping region, `None` when drawing is unrestricted
+    pub fn get_clip(&self) -> Option<Rect> {
+        self.clip_stack.last().copied()
+    }
+
+    /// Run `f` with drawing clipped to `rect`, popping the region afterwards
+    pub fn with_clip<R, F: FnOnce(&mut Self) -> R>(&mut self, rect: Rect, f: F) -> R {
+        self.push_clip(rect);
+        let result = f(self);
+        self.pop_clip();
+        result
+    }
+
+    /// Whether a point (in rotated coordinates) is outside the clipping region
+    fn is_clipped(&self, x: i32, y: i32) -> bool {
+        self.clip_stack.last().is_some_and(|clip| !clip.contains(x, y))
+    }
+
+    /// Draw a gray pixel at coordinates, considering rotation
+    ///
+    /// On a 1-bit buffer the two darker levels are drawn black.
+    pub fn draw_gray_pixel(&mut self, x: i32, y: i32, level: u8) {
+        if self.is_clipped(x, y) {
+            return;
+        }
+        let point = self.rotate_pixel(x, y);
+        if self.is_grayscale() {
+            self.draw_absolute_gray_pixel(point.0, point.1, level);
+        } else {
+            self.draw_absolute_pixel(point.0, point.1, level < GRAY_LIGHT);
+        }
+    }
+
     /// Draw a pixel at absolute coordinates (ignoring rotation)
     fn draw_absolute_pixel(&mut self, x: i32, y: i32, colored: bool) {
+        if self.is_grayscale() {
+            let level = if colored { self.gray_ink } else { GRAY_WHITE };
+            self.draw_absolute_gray_pixel(x, y, level);
+            return;
+        }
+
         if x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32 {
             return;
         }
@@ -84,22 +277,30 @@ impl Paint {
         // Calculate the byte position in the buffer
         let x_pos = x as u32;
         let y_pos = y as u32;
-        let addr = (x_pos / 8 + y_pos * (self.width / 8)) as usize;
+        let addr = (x_pos / 8 + y_pos * self.width.div_ceil(8)) as usize;
 
-        if addr >= self.image.len() {
+        let image = match (self.plane, self.chromatic.as_mut()) {
+            (ColorPlane::Chromatic, Some(chromatic)) => chromatic,
+            _ => &mut self.image,
+        };
+
+        if addr >= image.len() {
             return;
         }
 
         // Set the specific bit
         if colored {
-            self.image[addr] &= !(0x80 >> (x_pos % 8));
+            image[addr] &= !(0x80 >> (x_pos % 8));
         } else {
-            self.image[addr] |= 0x80 >> (x_pos % 8);
+            image[addr] |= 0x80 >> (x_pos % 8);
         }
     }
 
-    /// Draw a pixel at coordinates, considering rotation
+    /// Draw a pixel at coordinates, considering rotation and clipping
     pub fn draw_pixel(&mut self, x: i32, y: i32, colored: bool) {
+        if self.is_clipped(x, y) {
+            return;
+        }
         let point = self.rotate_pixel(x, y);
         self.draw_absolute_pixel(point.0, point.1, colored);
     }
@@ -117,6 +318,45 @@ impl Paint {
         }
     }
 
+    /// Draw a 1-bit bitmap with its top left corner at (x, y)
+    pub fn draw_bitmap(&mut self, x: i32, y: i32, bitmap: &Bitmap, mode: BitmapMode) {
+        for row in 0..bitmap.height() {
+            for col in 0..bitmap.width() {
+                let set = bitmap.get(col, row);
+                let colored = match mode {
+                    BitmapMode::Transparent if !set => continue,
+                    BitmapMode::Transparent | BitmapMode::Opaque => set,
+                    BitmapMode::Inverted => !set,
+                };
+                self.draw_pixel(x + col as i32, y + row as i32, colored);
+            }
+        }
+    }
+
+    /// Draw an image scaled to fill `rect`
+    ///
+    /// The image is tone adjusted and dithered to black and white, or to the
+    /// four gray levels of a grayscale buffer. Rotation and clipping apply
+    /// as for single pixels.
+    pub fn draw_image(&mut self, image: &GrayImage, rect: &Rect, style: &ImageStyle) {
+        if rect.width <= 0 || rect.height <= 0 {
+            return;
+        }
+        let scaled = image.scaled(rect.width as u32, rect.height as u32);
+        let levels = if self.is_grayscale() { 4 } else { 2 };
+        let quantized = scaled.quantize(levels, style);
+
+        for (index, &level) in quantized.iter().enumerate() {
+            let x = rect.x + index as i32 % rect.width;
+            let y = rect.y + index as i32 / rect.width;
+            if self.is_grayscale() {
+                self.draw_gray_pixel(x, y, level);
+            } else {
+                self.draw_pixel(x, y, level == 0);
+            }
+        }
+    }
+
     /// Draw a character at the specified position
     pub fn draw_char_at(&mut self, x: i32, y: i32, ascii_char: char, font: &crate::fonts::Font, colored: bool) {
         if !font.has_char(ascii_char) {
@@ -174,15 +414,9 @@ impl Paint {
         let dx = x1_copy - x0_copy;
         let dy = (y1_copy - y0_copy).abs();
         let mut err = dx / 2;
-        let mut y_step: i32;
+        let y_step = if y0_copy < y1_copy { 1 } else { -1 };
         let mut y = y0_copy;
 
-        if y0_copy < y1_copy {
-            y_step = 1;
-        } else {
-            y_step = -1;
-        }
-
         for x in x0_copy..=x1_copy {
             if steep {
                 self.draw_pixel(y, x, colored);
@@ -238,19 +472,34 @@ impl Paint {
 
     /// Draw a circle
     pub fn draw_circle(&mut self, x: i32, y: i32, radius: i32, colored: bool) {
+        for (dx, dy) in Self::circle_quadrant(radius) {
+            self.draw_pixel(x + dx, y + dy, colored);
+            self.draw_pixel(x - dx, y + dy, colored);
+            self.draw_pixel(x - dx, y - dy, colored);
+            self.draw_pixel(x + dx, y - dy, colored);
+        }
+    }
+
+    /// Draw a filled circle
+    pub fn draw_filled_circle(&mut self, x: i32, y: i32, radius: i32, colored: bool) {
+        for (dx, dy) in Self::circle_quadrant(radius) {
+            self.draw_horizontal_line(x - dx, y - dy, 2 * dx + 1, colored);
+            self.draw_horizontal_line(x - dx, y + dy, 2 * dx + 1, colored);
+        }
+    }
+
+    /// Offsets of the points of a circle quadrant, both coordinates >= 0
+    fn circle_quadrant(radius: i32) -> Vec<(i32, i32)> {
         // Bresenham's circle algorithm
+        let mut points = Vec::new();
         let mut x_pos = -radius;
         let mut y_pos = 0;
         let mut err = 2 - 2 * radius;
-        
-        loop {
-            self.draw_pixel(x - x_pos, y + y_pos, colored);
-            self.draw_pixel(x + x_pos, y + y_pos, colored);
-            self.draw_pixel(x + x_pos, y - y_pos, colored);
-            self.draw_pixel(x - x_pos, y - y_pos, colored);
-            
+
+        while x_pos <= 0 {
+            points.push((-x_pos, y_pos));
             let radius_err = err;
-            if y_pos < 0 && radius_err <= y_pos {
+            if radius_err <= y_pos {
                 y_pos += 1;
                 err += y_pos * 2 + 1;
             }
@@ -258,32 +507,623 @@ impl Paint {
                 x_pos += 1;
                 err += x_pos * 2 + 1;
             }
-            if x_pos > 0 {
-                break;
+        }
+        points
+    }
+
+    /// Draw a rectangle with rounded corners
+    ///
+    /// The radius is limited to half the shorter side.
+    pub fn draw_rounded_rectangle(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, radius: i32, colored: bool) {
+        let (min_x, max_x) = (min(x0, x1), max(x0, x1));
+        let (min_y, max_y) = (min(y0, y1), max(y0, y1));
+        let radius = radius.clamp(0, min(max_x - min_x, max_y - min_y) / 2);
+
+        // Centers of the corner arcs
+        let (left, right) = (min_x + radius, max_x - radius);
+        let (top, bottom) = (min_y + radius, max_y - radius);
+
+        self.draw_horizontal_line(left, min_y, right - left + 1, colored);
+        self.draw_horizontal_line(left, max_y, right - left + 1, colored);
+        self.draw_vertical_line(min_x, top, bottom - top + 1, colored);
+        self.draw_vertical_line(max_x, top, bottom - top + 1, colored);
+
+        for (dx, dy) in Self::circle_quadrant(radius) {
+            self.draw_pixel(left - dx, top - dy, colored);
+            self.draw_pixel(right + dx, top - dy, colored);
+            self.draw_pixel(left - dx, bottom + dy, colored);
+            self.draw_pixel(right + dx, bottom + dy, colored);
+        }
+    }
+
+    /// Draw a filled rectangle with rounded corners
+    pub fn draw_filled_rounded_rectangle(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, radius: i32, colored: bool) {
+        let (min_x, max_x) = (min(x0, x1), max(x0, x1));
+        let (min_y, max_y) = (min(y0, y1), max(y0, y1));
+        let radius = radius.clamp(0, min(max_x - min_x, max_y - min_y) / 2);
+
+        let (left, right) = (min_x + radius, max_x - radius);
+        let (top, bottom) = (min_y + radius, max_y - radius);
+
+        for y in top..=bottom {
+            self.draw_horizontal_line(min_x, y, max_x - min_x + 1, colored);
+        }
+        for (dx, dy) in Self::circle_quadrant(radius) {
+            self.draw_horizontal_line(left - dx, top - dy, right - left + 2 * dx + 1, colored);
+            self.draw_horizontal_line(left - dx, bottom + dy, right - left + 2 * dx + 1, colored);
+        }
+    }
+
+    /// Points of the first quadrant of an ellipse (midpoint algorithm)
+    fn ellipse_quadrant(rx: i32, ry: i32) -> Vec<(i32, i32)> {
+        // A flat ellipse is a line, region 1 would stop at its first point
+        if ry == 0 {
+            return (0..=rx).map(|x| (x, 0)).collect();
+        }
+        if rx == 0 {
+            return (0..=ry).map(|y| (0, y)).collect();
+        }
+
+        let (a2, b2) = ((rx as f64).powi(2), (ry as f64).powi(2));
+        let mut points = Vec::new();
+        let (mut x, mut y) = (0i32, ry);
+        let mut px = 0.0;
+        let mut py = 2.0 * a2 * y as f64;
+
+        // Region 1, slope above -1
+        let mut p = b2 - a2 * ry as f64 + a2 / 4.0;
+        while px < py {
+            points.push((x, y));
+            x += 1;
+            px += 2.0 * b2;
+            if p < 0.0 {
+                p += b2 + px;
+            } else {
+                y -= 1;
+                py -= 2.0 * a2;
+                p += b2 + px - py;
+            }
+        }
+
+        // Region 2, slope below -1
+        let mut p = b2 * (x as f64 + 0.5).powi(2) + a2 * ((y - 1) as f64).powi(2) - a2 * b2;
+        while y >= 0 {
+            points.push((x, y));
+            y -= 1;
+            py -= 2.0 * a2;
+            if p > 0.0 {
+                p += a2 - py;
+            } else {
+                x += 1;
+                px += 2.0 * b2;
+                p += a2 - py + px;
             }
         }
+        points
     }
 
-    /// Draw a filled circle
-    pub fn draw_filled_circle(&mut self, x: i32, y: i32, radius: i32, colored: bool) {
-        // Bresenham's circle algorithm with filling
-        let mut x_pos = -radius;
-        let mut y_pos = 0;
-        let mut err = 2 - 2 * radius;
-        
-        while x_pos <= 0 {
-            self.draw_horizontal_line(x + x_pos, y - y_pos, -2 * x_pos + 1, colored);
-            self.draw_horizontal_line(x + x_pos, y + y_pos, -2 * x_pos + 1, colored);
-            
-            let radius_err = err;
-            if y_pos < 0 && radius_err <= y_pos {
-                y_pos += 1;
-                err += y_pos * 2 + 1;
+    /// Draw an ellipse with the given center and radii
+    pub fn draw_ellipse(&mut self, x: i32, y: i32, radius_x: i32, radius_y: i32, colored: bool) {
+        if radius_x < 0 || radius_y < 0 {
+            return;
+        }
+        for (dx, dy) in Self::ellipse_quadrant(radius_x, radius_y) {
+            self.draw_pixel(x + dx, y + dy, colored);
+            self.draw_pixel(x - dx, y + dy, colored);
+            self.draw_pixel(x + dx, y - dy, colored);
+            self.draw_pixel(x - dx, y - dy, colored);
+        }
+    }
+
+    /// Draw a filled ellipse
+    pub fn draw_filled_ellipse(&mut self, x: i32, y: i32, radius_x: i32, radius_y: i32, colored: bool) {
+        if radius_x < 0 || radius_y < 0 {
+            return;
+        }
+        for (dx, dy) in Self::ellipse_quadrant(radius_x, radius_y) {
+            self.draw_horizontal_line(x - dx, y - dy, 2 * dx + 1, colored);
+            self.draw_horizontal_line(x - dx, y + dy, 2 * dx + 1, colored);
+        }
+    }
+
+    /// Whether an angle lies within the sweep starting at `start`
+    fn in_sweep(angle: f32, start: f32, sweep: f32) -> bool {
+        if sweep.abs() >= 360.0 {
+            return true;
+        }
+        let (from, length) = if sweep >= 0.0 { (start, sweep) } else { (start + sweep, -sweep) };
+        (angle - from).rem_euclid(360.0) <= length
+    }
+
+    /// Angle in degrees of an offset from the center, clockwise from 3 o'clock
+    fn angle_of(dx: i32, dy: i32) -> f32 {
+        (dy as f32).atan2(dx as f32).to_degrees().rem_euclid(360.0)
+    }
+
+    /// Draw a circular arc
+    ///
+    /// Angles are in degrees, 0 pointing right and growing clockwise; a
+    /// negative sweep runs counterclockwise from `start_angle`.
+    pub fn draw_arc(&mut self, x: i32, y: i32, radius: i32, start_angle: f32, sweep_angle: f32, colored: bool) {
+        if radius < 0 {
+            return;
+        }
+        for (dx, dy) in Self::circle_quadrant(radius) {
+            for (px, py) in [(dx, dy), (-dx, dy), (dx, -dy), (-dx, -dy)] {
+                if Self::in_sweep(Self::angle_of(px, py), start_angle, sweep_angle) {
+                    self.draw_pixel(x + px, y + py, colored);
+                }
             }
-            if radius_err > x_pos || err > y_pos {
-                x_pos += 1;
-                err += x_pos * 2 + 1;
+        }
+    }
+
+    /// Draw an arc `width` pixels wide, growing inwards from `radius`,
+    /// e.g. the band of a gauge
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_thick_arc(
+        &mut self,
+        x: i32,
+        y: i32,
+        radius: i32,
+        start_angle: f32,
+        sweep_angle: f32,
+        width: i32,
+        colored: bool,
+    ) {
+        if width <= 1 {
+            self.draw_arc(x, y, radius, start_angle, sweep_angle, colored);
+            return;
+        }
+        let outer = radius as f32 + 0.5;
+        let inner = (radius - width) as f32 + 0.5;
+        self.draw_sector(x, y, radius, inner, outer, start_angle, sweep_angle, colored);
+    }
+
+    /// Draw a filled pie slice
+    pub fn draw_filled_arc(&mut self, x: i32, y: i32, radius: i32, start_angle: f32, sweep_angle: f32, colored: bool) {
+        self.draw_sector(x, y, radius, -1.0, radius as f32 + 0.5, start_angle, sweep_angle, colored);
+    }
+
+    // Fill the pixels between two distances from the center within a sweep
+    #[allow(clippy::too_many_arguments)]
+    fn draw_sector(
+        &mut self,
+        x: i32,
+        y: i32,
+        radius: i32,
+        inner: f32,
+        outer: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+        colored: bool,
+    ) {
+        if radius < 0 {
+            return;
+        }
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                let inside = distance < outer && distance > inner;
+                if inside && (dx == 0 && dy == 0 || Self::in_sweep(Self::angle_of(dx, dy), start_angle, sweep_angle)) {
+                    self.draw_pixel(x + dx, y + dy, colored);
+                }
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Draw the outline of a closed polygon
+    pub fn draw_polygon(&mut self, points: &[(i32, i32)], colored: bool) {
+        for (i, &(x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(i + 1) % points.len()];
+            self.draw_line(x0, y0, x1, y1, colored);
+        }
+    }
+
+    /// Draw a filled polygon, self-intersecting polygons use the even-odd rule
+    pub fn draw_filled_polygon(&mut self, points: &[(i32, i32)], colored: bool) {
+        let vertices: Vec<(f32, f32)> = points.iter().map(|&(x, y)| (x as f32, y as f32)).collect();
+        self.fill_polygon(&vertices, colored);
+        self.draw_polygon(points, colored);
+    }
+
+    /// Draw the outline of a triangle
+    pub fn draw_triangle(&mut self, p0: (i32, i32), p1: (i32, i32), p2: (i32, i32), colored: bool) {
+        self.draw_polygon(&[p0, p1, p2], colored);
+    }
+
+    /// Draw a filled triangle, e.g. a trend arrow
+    pub fn draw_filled_triangle(&mut self, p0: (i32, i32), p1: (i32, i32), p2: (i32, i32), colored: bool) {
+        self.draw_filled_polygon(&[p0, p1, p2], colored);
+    }
+
+    /// Draw a line `width` pixels wide with square ends
+    pub fn draw_thick_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, width: i32, colored: bool) {
+        let (dx, dy) = ((x1 - x0) as f32, (y1 - y0) as f32);
+        let length = (dx * dx + dy * dy).sqrt();
+        if width <= 1 || length == 0.0 {
+            self.draw_line(x0, y0, x1, y1, colored);
+            return;
+        }
+
+        // Offset to both sides, perpendicular to the line, and half a pixel
+        // past the ends so the end points are covered
+        let half = width as f32 / 2.0;
+        let (nx, ny) = (-dy / length * half, dx / length * half);
+        let (ex, ey) = (dx / length * 0.5, dy / length * 0.5);
+        let (x0, y0) = (x0 as f32 - ex, y0 as f32 - ey);
+        let (x1, y1) = (x1 as f32 + ex, y1 as f32 + ey);
+        self.fill_polygon(
+            &[(x0 + nx, y0 + ny), (x1 + nx, y1 + ny), (x1 - nx, y1 - ny), (x0 - nx, y0 - ny)],
+            colored,
+        );
+    }
+
+    // Scanline fill of the pixels inside a polygon (even-odd rule), a pixel
+    // is inside when its coordinates are, with right and bottom edges excluded
+    fn fill_polygon(&mut self, points: &[(f32, f32)], colored: bool) {
+        if points.len() < 3 {
+            return;
+        }
+        let min_y = points.iter().map(|p| p.1).fold(f32::MAX, f32::min).ceil() as i32;
+        let max_y = points.iter().map(|p| p.1).fold(f32::MIN, f32::max).ceil() as i32;
+
+        let mut crossings = Vec::new();
+        for y in min_y..max_y {
+            let scan = y as f32;
+            crossings.clear();
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                // Half-open edges so shared vertices are counted once
+                if (y0 <= scan && scan < y1) || (y1 <= scan && scan < y0) {
+                    crossings.push(x0 + (scan - y0) * (x1 - x0) / (y1 - y0));
+                }
+            }
+            crossings.sort_by(f32::total_cmp);
+
+            for pair in crossings.chunks_exact(2) {
+                let (start, end) = (pair[0].ceil() as i32, pair[1].ceil() as i32);
+                if end > start {
+                    self.draw_horizontal_line(start, y, end - start, colored);
+                }
+            }
+        }
+    }
+}
+
+/// Drawing with `embedded-graphics`
+///
+/// `BinaryColor::On` draws ink like `COLORED`, `BinaryColor::Off` draws
+/// paper. Coordinates go through the rotation set with `set_rotate`, and the
+/// current plane and gray ink apply as for the other drawing functions.
+///
+/// ```
+/// use embedded_graphics::mono_font::{ascii::FONT_6X10, MonoTextStyle};
+/// use embedded_graphics::pixelcolor::BinaryColor;
+/// use embedded_graphics::prelude::*;
+/// use embedded_graphics::primitives::{Circle, PrimitiveStyle};
+/// use embedded_graphics::text::Text;
+/// use epaper_ui::display::{Paint, ROTATE_90};
+///
+/// let mut paint = Paint::new(128, 296);
+/// paint.set_rotate(ROTATE_90);
+///
+/// Circle::new(Point::new(10, 10), 40)
+///     .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 2))
+///     .draw(&mut paint)
+///     .unwrap();
+/// Text::new("Hello", Point::new(60, 30), MonoTextStyle::new(&FONT_6X10, BinaryColor::On))
+///     .draw(&mut paint)
+///     .unwrap();
+/// ```
+impl DrawTarget for Paint {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            self.draw_pixel(point.x, point.y, color.is_on());
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        Paint::clear(self, color.is_on());
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Paint {
+    /// Size as seen through the rotation
+    fn size(&self) -> Size {
+        match self.rotate {
+            ROTATE_90 | ROTATE_270 => Size::new(self.height, self.width),
+            _ => Size::new(self.width, self.height),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rows of the black plane, '#' for ink and '.' for paper
+    fn render(paint: &Paint) -> Vec<String> {
+        let stride = paint.get_width().div_ceil(8) as usize;
+        (0..paint.get_height() as usize)
+            .map(|y| {
+                (0..paint.get_width() as usize)
+                    .map(|x| match paint.get_image()[y * stride + x / 8] & (0x80 >> (x % 8)) {
+                        0 => '#',
+                        _ => '.',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn circles_are_round() {
+        let mut paint = Paint::new(16, 9);
+        paint.draw_circle(4, 4, 3, COLORED);
+        paint.draw_filled_circle(12, 4, 3, COLORED);
+        assert_eq!(
+            render(&paint),
+            [
+                "................",
+                "...###.....###..",
+                "..#...#...#####.",
+                ".#.....#.#######",
+                ".#.....#.#######",
+                ".#.....#.#######",
+                "..#...#...#####.",
+                "...###.....###..",
+                "................",
+            ]
+        );
+    }
+
+    #[test]
+    fn rounded_rectangles_cut_the_corners() {
+        let mut paint = Paint::new(24, 7);
+        paint.draw_rounded_rectangle(0, 0, 10, 6, 2, COLORED);
+        paint.draw_filled_rounded_rectangle(22, 6, 12, 0, 2, COLORED);
+        assert_eq!(
+            render(&paint),
+            [
+                ".#########...#########..",
+                "#.........#.###########.",
+                "#.........#.###########.",
+                "#.........#.###########.",
+                "#.........#.###########.",
+                "#.........#.###########.",
+                ".#########...#########..",
+            ]
+        );
+    }
+
+    #[test]
+    fn ellipses_span_both_radii() {
+        let mut paint = Paint::new(24, 9);
+        paint.draw_ellipse(5, 4, 5, 3, COLORED);
+        paint.draw_filled_ellipse(17, 4, 5, 3, COLORED);
+        assert_eq!(
+            render(&paint),
+            [
+                "........................",
+                "...#####.......#####....",
+                ".##.....##...#########..",
+                "#.........#.###########.",
+                "#.........#.###########.",
+                "#.........#.###########.",
+                ".##.....##...#########..",
+                "...#####.......#####....",
+                "........................",
+            ]
+        );
+    }
+
+    #[test]
+    fn ellipses_with_a_zero_radius_are_lines() {
+        let mut paint = Paint::new(32, 8);
+        paint.draw_ellipse(16, 4, 10, 0, COLORED);
+        paint.draw_ellipse(2, 3, 0, 2, COLORED);
+        let mut filled = Paint::new(32, 8);
+        filled.draw_filled_ellipse(16, 4, 10, 0, COLORED);
+        filled.draw_filled_ellipse(2, 3, 0, 2, COLORED);
+
+        let expected = [
+            "................................",
+            "..#.............................",
+            "..#.............................",
+            "..#.............................",
+            "..#...#####################.....",
+            "..#.............................",
+            "................................",
+            "................................",
+        ];
+        assert_eq!(render(&paint), expected);
+        assert_eq!(render(&filled), expected);
+    }
+
+    #[test]
+    fn arc_runs_clockwise_from_the_start_angle() {
+        let mut paint = Paint::new(11, 11);
+        paint.draw_arc(5, 5, 5, 0.0, 90.0, COLORED);
+        assert_eq!(
+            render(&paint),
+            [
+                "...........",
+                "...........",
+                "...........",
+                "...........",
+                "...........",
+                "..........#",
+                "..........#",
+                "..........#",
+                ".........#.",
+                "........#..",
+                ".....###...",
+            ]
+        );
+    }
+
+    #[test]
+    fn thick_arc_grows_inwards() {
+        let mut paint = Paint::new(11, 7);
+        paint.draw_thick_arc(5, 5, 5, 180.0, 180.0, 2, COLORED);
+        assert_eq!(
+            render(&paint),
+            [
+                "...#####...",
+                "..#######..",
+                ".###...###.",
+                "###.....###",
+                "##.......##",
+                "##.......##",
+                "...........",
+            ]
+        );
+    }
+
+    #[test]
+    fn filled_arc_is_a_pie_slice() {
+        let mut paint = Paint::new(9, 9);
+        paint.draw_filled_arc(4, 4, 4, -90.0, -90.0, COLORED);
+        assert_eq!(
+            render(&paint),
+            [
+                "..###....",
+                ".####....",
+                "#####....",
+                "#####....",
+                "#####....",
+                ".........",
+                ".........",
+                ".........",
+                ".........",
+            ]
+        );
+    }
+
+    #[test]
+    fn filled_polygons_include_their_outline() {
+        let mut paint = Paint::new(24, 8);
+        paint.draw_filled_polygon(&[(0, 0), (7, 0), (7, 7), (0, 7)], COLORED);
+        paint.draw_filled_triangle((12, 0), (20, 7), (12, 7), COLORED);
+        assert_eq!(
+            render(&paint),
+            [
+                "########....#...........",
+                "########....##..........",
+                "########....###.........",
+                "########....#####.......",
+                "########....######......",
+                "########....#######.....",
+                "########....########....",
+                "########....#########...",
+            ]
+        );
+    }
+
+    #[test]
+    fn thick_lines_are_centered_on_the_line() {
+        let mut paint = Paint::new(16, 8);
+        paint.draw_thick_line(1, 1, 12, 1, 3, COLORED);
+        paint.draw_thick_line(2, 5, 2, 6, 2, COLORED);
+        assert_eq!(
+            render(&paint),
+            [
+                ".############...",
+                ".############...",
+                ".############...",
+                "................",
+                "................",
+                ".##.............",
+                ".##.............",
+                "................",
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_clips_intersect() {
+        let mut paint = Paint::new(8, 4);
+        paint.push_clip(Rect::new(1, 0, 4, 4));
+        paint.push_clip(Rect::new(3, 1, 4, 2));
+        assert_eq!(paint.get_clip(), Some(Rect::new(3, 1, 2, 2)));
+
+        paint.draw_filled_rectangle(0, 0, 7, 3, COLORED);
+        assert_eq!(render(&paint), ["........", "...##...", "...##...", "........"]);
+    }
+
+    #[test]
+    fn pop_clip_restores_the_previous_clip() {
+        let mut paint = Paint::new(8, 4);
+        paint.push_clip(Rect::new(1, 0, 4, 4));
+        paint.push_clip(Rect::new(3, 1, 4, 2));
+        assert_eq!(paint.pop_clip(), Some(Rect::new(3, 1, 2, 2)));
+        assert_eq!(paint.get_clip(), Some(Rect::new(1, 0, 4, 4)));
+
+        paint.draw_filled_rectangle(0, 0, 7, 1, COLORED);
+        assert_eq!(render(&paint), [".####...", ".####...", "........", "........"]);
+
+        paint.pop_clip();
+        assert_eq!(paint.get_clip(), None);
+        paint.draw_line(0, 3, 7, 3, COLORED);
+        assert_eq!(render(&paint), [".####...", ".####...", "........", "########"]);
+    }
+
+    #[test]
+    fn clip_is_in_rotated_coordinates() {
+        // Seen through ROTATE_90 the panel is 4 wide and 8 high, and the
+        // rotated point (x, y) is drawn at (7 - y, x)
+        let mut paint = Paint::new(8, 4);
+        paint.set_rotate(ROTATE_90);
+        paint.push_clip(Rect::new(0, 0, 2, 3));
+        paint.draw_filled_rectangle(0, 0, 3, 7, COLORED);
+        assert_eq!(render(&paint), [".....###", ".....###", "........", "........"]);
+    }
+
+    #[test]
+    fn embedded_graphics_primitives_are_rotated() {
+        use embedded_graphics::prelude::*;
+        use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+
+        // The rotated point (x, y) is drawn at (7 - y, x)
+        let mut paint = Paint::new(8, 4);
+        paint.set_rotate(ROTATE_90);
+        Line::new(Point::new(0, 0), Point::new(3, 0))
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(&mut paint)
+            .unwrap();
+        Rectangle::new(Point::new(1, 5), Size::new(2, 2))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(&mut paint)
+            .unwrap();
+        assert_eq!(render(&paint), [".......#", ".##....#", ".##....#", ".......#"]);
+
+        // Paper clears the ink again
+        Pixel(Point::new(1, 5), BinaryColor::Off).draw(&mut paint).unwrap();
+        assert_eq!(render(&paint), [".......#", ".#.....#", ".##....#", ".......#"]);
+    }
+
+    #[test]
+    fn size_swaps_width_and_height_when_rotated_a_quarter_turn() {
+        use embedded_graphics::geometry::Dimensions;
+
+        let mut paint = Paint::new(8, 4);
+        for (rotate, size) in [
+            (ROTATE_0, Size::new(8, 4)),
+            (ROTATE_90, Size::new(4, 8)),
+            (ROTATE_180, Size::new(8, 4)),
+            (ROTATE_270, Size::new(4, 8)),
+        ] {
+            paint.set_rotate(rotate);
+            assert_eq!(paint.size(), size);
+            assert_eq!(paint.bounding_box().size, size);
+        }
+    }
+}
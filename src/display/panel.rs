@@ -0,0 +1,301 @@
+use crate::display::driver::RefreshCapabilities;
+use crate::display::lut::Waveform;
+
+/// Controller family, which determines the command set used by the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    /// UltraChip UC8176/UC8179 and the compatible IL0398 (BUSY low while busy)
+    Uc81xx,
+    /// Solomon SSD1675/SSD1680 (BUSY high while busy)
+    Ssd16xx,
+}
+
+impl Controller {
+    /// Level of the BUSY line while the controller is busy
+    pub fn busy_level(&self) -> bool {
+        match self {
+            Controller::Uc81xx => false,
+            Controller::Ssd16xx => true,
+        }
+    }
+}
+
+/// Colors a panel can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelColor {
+    /// Black and white only
+    BlackWhite,
+    /// Black and white, or four gray levels with the Gray4 waveform
+    Gray4,
+    /// Black, white and red
+    BlackWhiteRed,
+    /// Black, white and yellow
+    BlackWhiteYellow,
+}
+
+impl PanelColor {
+    /// Whether the panel has a second (red or yellow) color plane
+    pub fn is_tricolor(&self) -> bool {
+        matches!(self, PanelColor::BlackWhiteRed | PanelColor::BlackWhiteYellow)
+    }
+}
+
+/// One step of a panel initialization sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitStep {
+    /// Send a command followed by its data bytes
+    Command(u8, &'static [u8]),
+    /// Wait for the BUSY line to report idle
+    WaitIdle,
+    /// Sleep for the given number of milliseconds
+    Delay(u64),
+}
+
+/// Description of a panel model
+///
+/// The driver resets the controller, runs `init_sequence` and, for panels
+/// with `custom_luts`, loads the lookup tables of the selected waveform.
+#[derive(Debug)]
+pub struct Panel {
+    /// Short identifier used to select the panel at runtime
+    pub name: &'static str,
+    /// Human readable model name
+    pub description: &'static str,
+    /// Width in pixels (the short side for SSD16xx panels)
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    pub controller: Controller,
+    pub init_sequence: &'static [InitStep],
+    /// Waveforms are loaded from registers instead of the panel OTP; on
+    /// UC81xx these map old/new pixel pairs, so the previous frame is sent
+    /// along with the new one
+    pub custom_luts: bool,
+    pub color: PanelColor,
+    /// The panel can refresh a window without a full-screen refresh
+    pub partial_refresh: bool,
+    /// UC81xx: the new image (DTM2) uses a set bit for ink, so frames are
+    /// sent inverted
+    pub inverted_data: bool,
+}
+
+impl Panel {
+    /// Whether the driver can load the given waveform on this panel
+    pub fn supports_waveform(&self, waveform: Waveform) -> bool {
+        match waveform {
+            Waveform::Full => true,
+            Waveform::Fast => self.custom_luts,
+            Waveform::Gray4 => self.custom_luts && self.color == PanelColor::Gray4,
+        }
+    }
+
+    /// Refresh modes the driver supports on this panel
+    pub fn capabilities(&self) -> RefreshCapabilities {
+        RefreshCapabilities {
+            partial: self.partial_refresh,
+            fast: self.supports_waveform(Waveform::Fast),
+            grayscale: self.supports_waveform(Waveform::Gray4),
+            color: self.color.is_tricolor(),
+        }
+    }
+}
+
+// Init sequence of the 2.13" V2, 250 gate lines
+static EPD_2IN13_V2_INIT: &[InitStep] = &[
+    InitStep::WaitIdle,
+    InitStep::Command(0x12, &[]), // SW_RESET
+    InitStep::WaitIdle,
+    InitStep::Command(0x01, &[0xF9, 0x00, 0x00]), // DRIVER_OUTPUT_CONTROL
+    InitStep::Command(0x11, &[0x03]),             // DATA_ENTRY_MODE: X+, Y+
+    InitStep::Command(0x44, &[0x00, 0x0F]),       // SET_RAM_X_RANGE
+    InitStep::Command(0x45, &[0x00, 0x00, 0xF9, 0x00]), // SET_RAM_Y_RANGE
+    InitStep::Command(0x3C, &[0x05]),             // BORDER_WAVEFORM
+    InitStep::Command(0x21, &[0x00, 0x80]),       // DISPLAY_UPDATE_CONTROL_1
+    InitStep::Command(0x18, &[0x80]),             // Internal temperature sensor
+    InitStep::Command(0x4E, &[0x00]),             // SET_RAM_X_COUNTER
+    InitStep::Command(0x4F, &[0x00, 0x00]),       // SET_RAM_Y_COUNTER
+    InitStep::WaitIdle,
+];
+
+// Init sequence of the 2.9" V2, 296 gate lines
+static EPD_2IN9_V2_INIT: &[InitStep] = &[
+    InitStep::WaitIdle,
+    InitStep::Command(0x12, &[]), // SW_RESET
+    InitStep::WaitIdle,
+    InitStep::Command(0x01, &[0x27, 0x01, 0x00]), // DRIVER_OUTPUT_CONTROL
+    InitStep::Command(0x11, &[0x03]),             // DATA_ENTRY_MODE: X+, Y+
+    InitStep::Command(0x44, &[0x00, 0x0F]),       // SET_RAM_X_RANGE
+    InitStep::Command(0x45, &[0x00, 0x00, 0x27, 0x01]), // SET_RAM_Y_RANGE
+    InitStep::Command(0x3C, &[0x05]),             // BORDER_WAVEFORM
+    InitStep::Command(0x21, &[0x00, 0x80]),       // DISPLAY_UPDATE_CONTROL_1
+    InitStep::Command(0x18, &[0x80]),             // Internal temperature sensor
+    InitStep::Command(0x4E, &[0x00]),             // SET_RAM_X_COUNTER
+    InitStep::Command(0x4F, &[0x00, 0x00]),       // SET_RAM_Y_COUNTER
+    InitStep::WaitIdle,
+];
+
+// Init sequence of the 2.13" B V4; unlike the B/W panels the red RAM is used
+static EPD_2IN13B_V4_INIT: &[InitStep] = &[
+    InitStep::WaitIdle,
+    InitStep::Command(0x12, &[]), // SW_RESET
+    InitStep::WaitIdle,
+    InitStep::Command(0x01, &[0xF9, 0x00, 0x00]), // DRIVER_OUTPUT_CONTROL
+    InitStep::Command(0x11, &[0x03]),             // DATA_ENTRY_MODE: X+, Y+
+    InitStep::Command(0x44, &[0x00, 0x0F]),       // SET_RAM_X_RANGE
+    InitStep::Command(0x45, &[0x00, 0x00, 0xF9, 0x00]), // SET_RAM_Y_RANGE
+    InitStep::Command(0x3C, &[0x05]),             // BORDER_WAVEFORM
+    InitStep::Command(0x21, &[0x80, 0x80]),       // DISPLAY_UPDATE_CONTROL_1
+    InitStep::Command(0x18, &[0x80]),             // Internal temperature sensor
+    InitStep::Command(0x4E, &[0x00]),             // SET_RAM_X_COUNTER
+    InitStep::Command(0x4F, &[0x00, 0x00]),       // SET_RAM_Y_COUNTER
+    InitStep::WaitIdle,
+];
+
+/// Waveshare 2.13" V2, 122x250 (SSD1675B)
+pub static EPD_2IN13_V2: Panel = Panel {
+    name: "epd2in13_v2",
+    description: "Waveshare 2.13\" V2 (122x250)",
+    width: 122,
+    height: 250,
+    controller: Controller::Ssd16xx,
+    init_sequence: EPD_2IN13_V2_INIT,
+    custom_luts: false,
+    color: PanelColor::BlackWhite,
+    partial_refresh: true,
+    inverted_data: false,
+};
+
+/// Waveshare 2.9" V2, 128x296 (SSD1680)
+pub static EPD_2IN9_V2: Panel = Panel {
+    name: "epd2in9_v2",
+    description: "Waveshare 2.9\" V2 (128x296)",
+    width: 128,
+    height: 296,
+    controller: Controller::Ssd16xx,
+    init_sequence: EPD_2IN9_V2_INIT,
+    custom_luts: false,
+    color: PanelColor::BlackWhite,
+    partial_refresh: true,
+    inverted_data: false,
+};
+
+/// Waveshare 2.13" B V4, 122x250 black/white/red (SSD1680)
+pub static EPD_2IN13B_V4: Panel = Panel {
+    name: "epd2in13b_v4",
+    description: "Waveshare 2.13\" B V4 (122x250, red)",
+    width: 122,
+    height: 250,
+    controller: Controller::Ssd16xx,
+    init_sequence: EPD_2IN13B_V4_INIT,
+    custom_luts: false,
+    color: PanelColor::BlackWhiteRed,
+    partial_refresh: false,
+    inverted_data: false,
+};
+
+/// Waveshare 4.2", 400x300 (IL0398)
+pub static EPD_4IN2: Panel = Panel {
+    name: "epd4in2",
+    description: "Waveshare 4.2\" (400x300)",
+    width: 400,
+    height: 300,
+    controller: Controller::Uc81xx,
+    init_sequence: &[
+        InitStep::Command(0x01, &[0x03, 0x00, 0x2B, 0x2B, 0x03]), // POWER_SETTING
+        InitStep::Command(0x06, &[0x17, 0x17, 0x17]),             // BOOSTER_SOFT_START
+        InitStep::Command(0x04, &[]),                             // POWER_ON
+        InitStep::WaitIdle,
+        InitStep::Command(0x00, &[0xBF, 0x0D]), // PANEL_SETTING: LUT from register
+        InitStep::Command(0x61, &[0x01, 0x90, 0x01, 0x2C]), // RESOLUTION_SETTING
+        InitStep::Command(0x82, &[0x28]),       // VCM_DC_SETTING
+        InitStep::Command(0x50, &[0x97]),       // VCOM_AND_DATA_INTERVAL_SETTING
+    ],
+    custom_luts: true,
+    color: PanelColor::Gray4,
+    partial_refresh: true,
+    inverted_data: false,
+};
+
+/// Waveshare 4.2" B V2, 400x300 black/white/red (UC8176)
+pub static EPD_4IN2B_V2: Panel = Panel {
+    name: "epd4in2b_v2",
+    description: "Waveshare 4.2\" B V2 (400x300, red)",
+    width: 400,
+    height: 300,
+    controller: Controller::Uc81xx,
+    init_sequence: &[
+        InitStep::Command(0x04, &[]), // POWER_ON
+        InitStep::WaitIdle,
+        InitStep::Command(0x00, &[0x0F]), // PANEL_SETTING: KWR mode, LUT from OTP
+    ],
+    custom_luts: false,
+    color: PanelColor::BlackWhiteRed,
+    partial_refresh: false,
+    inverted_data: false,
+};
+
+/// Waveshare 4.2" C, 400x300 black/white/yellow (IL0398)
+pub static EPD_4IN2C: Panel = Panel {
+    name: "epd4in2c",
+    description: "Waveshare 4.2\" C (400x300, yellow)",
+    width: 400,
+    height: 300,
+    controller: Controller::Uc81xx,
+    init_sequence: &[
+        InitStep::Command(0x06, &[0x17, 0x17, 0x17]), // BOOSTER_SOFT_START
+        InitStep::Command(0x04, &[]),                 // POWER_ON
+        InitStep::WaitIdle,
+        InitStep::Command(0x00, &[0x0F]), // PANEL_SETTING: KWR mode, LUT from OTP
+    ],
+    custom_luts: false,
+    color: PanelColor::BlackWhiteYellow,
+    partial_refresh: false,
+    inverted_data: false,
+};
+
+/// Waveshare 7.5" V2, 800x480 (UC8179)
+pub static EPD_7IN5_V2: Panel = Panel {
+    name: "epd7in5_v2",
+    description: "Waveshare 7.5\" V2 (800x480)",
+    width: 800,
+    height: 480,
+    controller: Controller::Uc81xx,
+    init_sequence: &[
+        InitStep::Command(0x01, &[0x07, 0x07, 0x3F, 0x3F]), // POWER_SETTING
+        InitStep::Command(0x06, &[0x17, 0x17, 0x28, 0x17]), // BOOSTER_SOFT_START
+        InitStep::Command(0x04, &[]),                       // POWER_ON
+        InitStep::Delay(100),
+        InitStep::WaitIdle,
+        InitStep::Command(0x00, &[0x1F]), // PANEL_SETTING: KW mode, LUT from OTP
+        InitStep::Command(0x61, &[0x03, 0x20, 0x01, 0xE0]), // RESOLUTION_SETTING
+        InitStep::Command(0x15, &[0x00]),       // DUAL_SPI off
+        InitStep::Command(0x50, &[0x10, 0x07]), // VCOM_AND_DATA_INTERVAL_SETTING
+        InitStep::Command(0x60, &[0x22]),       // TCON_SETTING
+    ],
+    custom_luts: false,
+    color: PanelColor::BlackWhite,
+    partial_refresh: true,
+    inverted_data: true,
+};
+
+/// All supported panels
+pub static PANELS: &[&Panel] = &[
+    &EPD_2IN13_V2,
+    &EPD_2IN13B_V4,
+    &EPD_2IN9_V2,
+    &EPD_4IN2,
+    &EPD_4IN2B_V2,
+    &EPD_4IN2C,
+    &EPD_7IN5_V2,
+];
+
+/// Panel used when none is configured
+pub static DEFAULT_PANEL: &Panel = &EPD_7IN5_V2;
+
+/// Look up a panel by its `name`, ignoring case
+pub fn find_panel(name: &str) -> Option<&'static Panel> {
+    PANELS
+        .iter()
+        .copied()
+        .find(|panel| panel.name.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,337 @@
+use crate::display::driver::{DisplayDriver, RefreshCapabilities};
+use crate::display::error::Result;
+use crate::ui::Rect;
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// How often and how patiently `RecoveringDriver` retries
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Recovery attempts after the first failure
+    pub max_retries: u32,
+    /// Wait before the first recovery attempt
+    pub initial_backoff: Duration,
+    /// Factor applied to the wait after every failed attempt
+    pub backoff_multiplier: f32,
+    /// Upper bound of the wait
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            backoff_multiplier: 2.0,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Values below 1.0 are treated as 1.0
+    pub fn backoff_multiplier(mut self, multiplier: f32) -> Self {
+        self.backoff_multiplier = multiplier.max(1.0);
+        self
+    }
+
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Wait before the given recovery attempt (starting at 1)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.backoff_multiplier.powi(attempt.saturating_sub(1) as i32);
+        self.initial_backoff.mul_f32(factor).min(self.max_backoff)
+    }
+}
+
+/// Wrapper that recovers a driver from bus and pin errors
+///
+/// When an operation fails with a recoverable error, the panel is reset and
+/// re-initialized with `init`, then the operation is retried with the same
+/// frame. Attempts back off according to the `RetryPolicy` and are logged.
+/// After a partial refresh is retried on a re-initialized panel, the area
+/// outside the window may need a full refresh.
+pub struct RecoveringDriver<D: DisplayDriver> {
+    driver: D,
+    policy: RetryPolicy,
+    recoveries: u64,
+}
+
+impl<D: DisplayDriver> RecoveringDriver<D> {
+    pub fn new(driver: D) -> Self {
+        Self::with_policy(driver, RetryPolicy::default())
+    }
+
+    pub fn with_policy(driver: D, policy: RetryPolicy) -> Self {
+        Self {
+            driver,
+            policy,
+            recoveries: 0,
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: RetryPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Number of successful recoveries so far
+    pub fn recoveries(&self) -> u64 {
+        self.recoveries
+    }
+
+    /// The wrapped driver
+    pub fn inner(&self) -> &D {
+        &self.driver
+    }
+
+    /// Mutable access to the wrapped driver
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.driver
+    }
+
+    /// Release the wrapped driver
+    pub fn into_inner(self) -> D {
+        self.driver
+    }
+
+    // Run an operation, resetting the panel and retrying after recoverable
+    // errors; `resets` marks an operation that resets the panel itself
+    fn run<F>(&mut self, operation: &str, resets: bool, mut f: F) -> Result<()>
+    where
+        F: FnMut(&mut D) -> Result<()>,
+    {
+        let mut error = match f(&mut self.driver) {
+            Ok(()) => return Ok(()),
+            Err(error) if error.is_recoverable() => error,
+            Err(error) => return Err(error),
+        };
+
+        for attempt in 1..=self.policy.max_retries {
+            let backoff = self.policy.backoff(attempt);
+            log::warn!(
+                "{} failed: {}; recovery attempt {}/{} in {:?}",
+                operation,
+                error,
+                attempt,
+                self.policy.max_retries,
+                backoff
+            );
+            thread::sleep(backoff);
+
+            // init resets the controller before configuring it
+            let result = if resets {
+                f(&mut self.driver)
+            } else {
+                self.driver.init().and_then(|()| f(&mut self.driver))
+            };
+            match result {
+                Ok(()) => {
+                    log::info!("{} succeeded after {} recovery attempt(s)", operation, attempt);
+                    self.recoveries += 1;
+                    return Ok(());
+                }
+                Err(next) if next.is_recoverable() => error = next,
+                Err(next) => {
+                    log::error!("{} failed during recovery: {}", operation, next);
+                    return Err(next);
+                }
+            }
+        }
+
+        log::error!(
+            "{} failed after {} recovery attempt(s): {}",
+            operation,
+            self.policy.max_retries,
+            error
+        );
+        Err(error)
+    }
+}
+
+impl<D: DisplayDriver> DisplayDriver for RecoveringDriver<D> {
+    fn width(&self) -> u32 {
+        self.driver.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.driver.height()
+    }
+
+    fn init(&mut self) -> Result<()> {
+        self.run("init", true, |driver| driver.init())
+    }
+
+    fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+        self.run("display_frame", false, |driver| driver.display_frame(frame_buffer))
+    }
+
+    fn display_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        self.run("display_partial", false, |driver| driver.display_partial(rect, buffer))
+    }
+
+    fn display_gray_frame(&mut self, gray_buffer: &[u8]) -> Result<()> {
+        self.run("display_gray_frame", false, |driver| driver.display_gray_frame(gray_buffer))
+    }
+
+    fn display_color_frame(&mut self, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        self.run("display_color_frame", false, |driver| {
+            driver.display_color_frame(black, chromatic)
+        })
+    }
+
+    fn sleep(&mut self) -> Result<()> {
+        self.run("sleep", false, |driver| driver.sleep())
+    }
+
+    fn capabilities(&self) -> RefreshCapabilities {
+        self.driver.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::error::EpdError;
+    use std::time::Instant;
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Init,
+        Frame(Vec<u8>),
+    }
+
+    // Driver failing a number of init calls with a busy timeout, and frames
+    // with the given errors, last one first
+    struct FlakyDriver {
+        failures: u32,
+        frame_errors: Vec<EpdError>,
+        calls: Vec<Call>,
+    }
+
+    impl FlakyDriver {
+        fn inits(&self) -> usize {
+            self.calls.iter().filter(|call| **call == Call::Init).count()
+        }
+    }
+
+    impl DisplayDriver for FlakyDriver {
+        fn width(&self) -> u32 {
+            8
+        }
+
+        fn height(&self) -> u32 {
+            1
+        }
+
+        fn init(&mut self) -> Result<()> {
+            self.calls.push(Call::Init);
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(EpdError::BusyTimeout(Duration::ZERO));
+            }
+            Ok(())
+        }
+
+        fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+            self.calls.push(Call::Frame(frame_buffer.to_vec()));
+            match self.frame_errors.pop() {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        }
+
+        fn sleep(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn flaky(failures: u32, frame_errors: Vec<EpdError>) -> FlakyDriver {
+        FlakyDriver {
+            failures,
+            frame_errors,
+            calls: Vec::new(),
+        }
+    }
+
+    fn recovering(failures: u32) -> RecoveringDriver<FlakyDriver> {
+        RecoveringDriver::with_policy(
+            flaky(failures, Vec::new()),
+            RetryPolicy::new().initial_backoff(Duration::ZERO),
+        )
+    }
+
+    #[test]
+    fn retried_init_runs_once_per_attempt() {
+        let mut driver = recovering(2);
+        driver.init().unwrap();
+        assert_eq!(driver.inner().inits(), 3);
+        assert_eq!(driver.recoveries(), 1);
+    }
+
+    #[test]
+    fn failed_init_gives_up_after_the_retries() {
+        let mut driver = recovering(10);
+        assert!(matches!(driver.init(), Err(EpdError::BusyTimeout(_))));
+        assert_eq!(driver.inner().inits(), 1 + DEFAULT_MAX_RETRIES as usize);
+    }
+
+    #[test]
+    fn recoverable_frame_error_reinitializes_and_resends_the_frame() {
+        let errors = vec![
+            EpdError::Spi(embedded_hal::spi::ErrorKind::Other),
+            EpdError::BusyTimeout(Duration::ZERO),
+        ];
+        let policy = RetryPolicy::new().initial_backoff(Duration::from_millis(20));
+        let mut driver = RecoveringDriver::with_policy(flaky(0, errors), policy);
+
+        let start = Instant::now();
+        driver.display_frame(&[0x5A]).unwrap();
+        // 20 ms before the first attempt and 40 ms before the second
+        assert!(start.elapsed() >= Duration::from_millis(60));
+
+        let frame = || Call::Frame(vec![0x5A]);
+        assert_eq!(
+            driver.inner().calls,
+            [frame(), Call::Init, frame(), Call::Init, frame()]
+        );
+        assert_eq!(driver.recoveries(), 1);
+    }
+
+    #[test]
+    fn non_recoverable_frame_error_is_not_retried() {
+        let errors = vec![EpdError::FrameSizeMismatch { expected: 1, actual: 2 }];
+        let mut driver = RecoveringDriver::new(flaky(0, errors));
+
+        let start = Instant::now();
+        let error = driver.display_frame(&[0x00, 0x00]);
+        assert!(matches!(error, Err(EpdError::FrameSizeMismatch { .. })));
+        assert!(start.elapsed() < DEFAULT_INITIAL_BACKOFF);
+        assert_eq!(driver.inner().calls, [Call::Frame(vec![0x00, 0x00])]);
+        assert_eq!(driver.recoveries(), 0);
+    }
+}
@@ -0,0 +1,532 @@
+use crate::display::driver::{
+    byte_aligned, changed_region, check_buffer_size, crop_window, paste_window, validate_window,
+    window_size, DisplayDriver, RefreshCapabilities,
+};
+use crate::display::error::{EpdError, Result};
+use crate::ui::Rect;
+use std::time::{Duration, Instant};
+
+// Waveshare recommends a full refresh after a few partial refreshes and at
+// least once a day
+pub const DEFAULT_MAX_PARTIAL_REFRESHES: u32 = 5;
+pub const DEFAULT_MAX_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+pub const DEFAULT_MAX_PARTIAL_AREA: f32 = 0.5;
+
+/// When partial refreshes are replaced by a full refresh to clear ghosting
+///
+/// Each limit can be disabled with `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RefreshPolicy {
+    /// Number of partial refreshes allowed between two full refreshes
+    pub max_partial_refreshes: Option<u32>,
+    /// Longest time since the last full refresh before the next one
+    pub max_interval: Option<Duration>,
+    /// Largest changed area, as a fraction of the panel, refreshed partially
+    pub max_partial_area: Option<f32>,
+}
+
+impl Default for RefreshPolicy {
+    fn default() -> Self {
+        Self {
+            max_partial_refreshes: Some(DEFAULT_MAX_PARTIAL_REFRESHES),
+            max_interval: Some(DEFAULT_MAX_INTERVAL),
+            max_partial_area: Some(DEFAULT_MAX_PARTIAL_AREA),
+        }
+    }
+}
+
+impl RefreshPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy that never forces a full refresh
+    pub fn unlimited() -> Self {
+        Self {
+            max_partial_refreshes: None,
+            max_interval: None,
+            max_partial_area: None,
+        }
+    }
+
+    pub fn max_partial_refreshes(mut self, count: Option<u32>) -> Self {
+        self.max_partial_refreshes = count;
+        self
+    }
+
+    pub fn max_interval(mut self, interval: Option<Duration>) -> Self {
+        self.max_interval = interval;
+        self
+    }
+
+    /// `fraction` is clamped to 0.0..=1.0
+    pub fn max_partial_area(mut self, fraction: Option<f32>) -> Self {
+        self.max_partial_area = fraction.map(|fraction| fraction.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Why a partial refresh should be replaced by a full one, if it should
+    ///
+    /// `area` is the fraction of the panel covered by the partial refresh.
+    pub fn full_refresh_reason(
+        &self,
+        partial_count: u32,
+        since_full: Option<Duration>,
+        area: f32,
+    ) -> Option<&'static str> {
+        if self.max_partial_refreshes.is_some_and(|max| partial_count >= max) {
+            return Some("partial refresh limit reached");
+        }
+        if let (Some(max), Some(elapsed)) = (self.max_interval, since_full) {
+            if elapsed >= max {
+                return Some("last full refresh too old");
+            }
+        }
+        if self.max_partial_area.is_some_and(|max| area > max) {
+            return Some("changed area too large");
+        }
+        None
+    }
+}
+
+/// What `RefreshManager::update` did with a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshOutcome {
+    /// The frame matched the panel contents, nothing was sent
+    Unchanged,
+    /// Only the given byte aligned window was refreshed
+    Partial(Rect),
+    /// The whole panel was refreshed
+    Full,
+}
+
+/// Wrapper that remembers the last frame sent to a driver
+///
+/// Identical frames are skipped, which saves power and panel wear for apps
+/// that redraw on a timer. `update` also refreshes only the changed area
+/// when the driver supports partial refresh, and falls back to a full
+/// refresh whenever the `RefreshPolicy` asks for one. A partial refresh sent
+/// through `display_partial` while the panel contents are unknown fails with
+/// `EpdError::FullRefreshRequired` instead, until a full frame is sent.
+pub struct RefreshManager<D: DisplayDriver> {
+    driver: D,
+    last_frame: Option<Vec<u8>>,
+    policy: RefreshPolicy,
+    partial_count: u32,
+    last_full: Option<Instant>,
+}
+
+impl<D: DisplayDriver> RefreshManager<D> {
+    pub fn new(driver: D) -> Self {
+        Self::with_policy(driver, RefreshPolicy::default())
+    }
+
+    pub fn with_policy(driver: D, policy: RefreshPolicy) -> Self {
+        Self {
+            driver,
+            last_frame: None,
+            policy,
+            partial_count: 0,
+            last_full: None,
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: RefreshPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> &RefreshPolicy {
+        &self.policy
+    }
+
+    /// Partial refreshes since the last full refresh
+    pub fn partial_count(&self) -> u32 {
+        self.partial_count
+    }
+
+    /// Time of the last full refresh
+    pub fn last_full_refresh(&self) -> Option<Instant> {
+        self.last_full
+    }
+
+    /// The wrapped driver
+    pub fn inner(&self) -> &D {
+        &self.driver
+    }
+
+    /// Mutable access to the wrapped driver
+    ///
+    /// Frames sent directly to the driver are not tracked; call `invalidate`
+    /// afterwards.
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.driver
+    }
+
+    /// Release the wrapped driver
+    pub fn into_inner(self) -> D {
+        self.driver
+    }
+
+    /// The last frame shown on the panel, if known
+    pub fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+
+    /// Forget the panel contents so the next frame is always sent in full
+    pub fn invalidate(&mut self) {
+        self.last_frame = None;
+    }
+
+    /// Pixels that differ between the panel contents and `frame`
+    ///
+    /// Returns the whole panel when the contents are unknown.
+    pub fn changed_region(&self, frame: &[u8]) -> Option<Rect> {
+        match &self.last_frame {
+            Some(last) => changed_region(last, frame, self.driver.width()),
+            None => Some(Rect::new(
+                0,
+                0,
+                self.driver.width() as i32,
+                self.driver.height() as i32,
+            )),
+        }
+    }
+
+    /// Show a frame with the cheapest refresh that produces it
+    pub fn update(&mut self, frame: &[u8]) -> Result<RefreshOutcome> {
+        check_buffer_size(frame, self.driver.frame_size())?;
+
+        let window = match (&self.last_frame, self.changed_region(frame)) {
+            (_, None) => return Ok(RefreshOutcome::Unchanged),
+            (Some(_), Some(region)) if self.driver.capabilities().partial => byte_aligned(&region),
+            _ => {
+                self.send_full(frame)?;
+                return Ok(RefreshOutcome::Full);
+            }
+        };
+
+        if let Some(reason) = self.full_refresh_reason(&window) {
+            log::info!("Full refresh instead of partial: {}", reason);
+            self.send_full(frame)?;
+            return Ok(RefreshOutcome::Full);
+        }
+
+        let buffer = crop_window(frame, self.driver.width(), &window);
+        self.send_partial(&window, &buffer)?;
+        Ok(RefreshOutcome::Partial(window))
+    }
+
+    // Ask the policy whether a partial refresh of `window` is still allowed
+    fn full_refresh_reason(&self, window: &Rect) -> Option<&'static str> {
+        let panel_area = self.driver.width() as f32 * self.driver.height() as f32;
+        let area = (window.width * window.height) as f32 / panel_area;
+        self.policy.full_refresh_reason(
+            self.partial_count,
+            self.last_full.map(|time| time.elapsed()),
+            area,
+        )
+    }
+
+    // Send a full frame and remember it
+    fn send_full(&mut self, frame: &[u8]) -> Result<()> {
+        // Unknown contents if the refresh fails half way
+        self.last_frame = None;
+        self.driver.display_frame(frame)?;
+        self.last_frame = Some(frame.to_vec());
+        self.partial_count = 0;
+        self.last_full = Some(Instant::now());
+        Ok(())
+    }
+
+    // Send a partial refresh and apply it to the remembered frame
+    fn send_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        self.driver.display_partial(rect, buffer)?;
+        if let Some(last) = self.last_frame.as_mut() {
+            paste_window(last, self.driver.width(), rect, buffer);
+        }
+        self.partial_count += 1;
+        Ok(())
+    }
+}
+
+impl<D: DisplayDriver> DisplayDriver for RefreshManager<D> {
+    fn width(&self) -> u32 {
+        self.driver.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.driver.height()
+    }
+
+    fn init(&mut self) -> Result<()> {
+        self.invalidate();
+        self.driver.init()
+    }
+
+    // Skip identical frames, otherwise do a full refresh
+    fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+        if self.last_frame.as_deref() == Some(frame_buffer) {
+            log::debug!("Frame unchanged, skipping refresh");
+            return Ok(());
+        }
+        self.send_full(frame_buffer)
+    }
+
+    // Partial refresh, promoted to a full one when the policy requires it
+    fn display_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        validate_window(rect, self.driver.width(), self.driver.height())?;
+        check_buffer_size(buffer, window_size(rect))?;
+        if let Some(reason) = self.full_refresh_reason(rect) {
+            // Without the panel contents the window cannot be widened to a
+            // full frame, and sending it anyway would never clear the ghosting
+            let Some(last) = &self.last_frame else {
+                return Err(EpdError::FullRefreshRequired(reason));
+            };
+            log::info!("Full refresh instead of partial: {}", reason);
+            let mut frame = last.clone();
+            paste_window(&mut frame, self.driver.width(), rect, buffer);
+            return self.send_full(&frame);
+        }
+        self.send_partial(rect, buffer)
+    }
+
+    fn display_gray_frame(&mut self, gray_buffer: &[u8]) -> Result<()> {
+        self.invalidate();
+        self.driver.display_gray_frame(gray_buffer)?;
+        self.partial_count = 0;
+        self.last_full = Some(Instant::now());
+        Ok(())
+    }
+
+    fn display_color_frame(&mut self, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        self.invalidate();
+        self.driver.display_color_frame(black, chromatic)?;
+        self.partial_count = 0;
+        self.last_full = Some(Instant::now());
+        Ok(())
+    }
+
+    fn sleep(&mut self) -> Result<()> {
+        self.driver.sleep()
+    }
+
+    fn capabilities(&self) -> RefreshCapabilities {
+        self.driver.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::error::EpdError;
+    use crate::display::simulated::SimulatedDisplay;
+
+    // 32x8 panel with partial refresh that records what it is sent
+    #[derive(Default)]
+    struct RecordingDriver {
+        frames: Vec<Vec<u8>>,
+        partials: Vec<(Rect, Vec<u8>)>,
+    }
+
+    impl DisplayDriver for RecordingDriver {
+        fn width(&self) -> u32 {
+            32
+        }
+
+        fn height(&self) -> u32 {
+            8
+        }
+
+        fn init(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+            self.frames.push(frame_buffer.to_vec());
+            Ok(())
+        }
+
+        fn display_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+            self.partials.push((*rect, buffer.to_vec()));
+            Ok(())
+        }
+
+        fn sleep(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn capabilities(&self) -> RefreshCapabilities {
+            RefreshCapabilities {
+                partial: true,
+                ..RefreshCapabilities::default()
+            }
+        }
+    }
+
+    fn white() -> Vec<u8> {
+        vec![0xFF; 32]
+    }
+
+    // Manager with a white frame on the panel
+    fn manager_with(policy: RefreshPolicy) -> RefreshManager<RecordingDriver> {
+        let mut manager = RefreshManager::with_policy(RecordingDriver::default(), policy);
+        assert_eq!(manager.update(&white()).unwrap(), RefreshOutcome::Full);
+        manager
+    }
+
+    #[test]
+    fn identical_frames_are_not_sent() {
+        let mut manager = manager_with(RefreshPolicy::unlimited());
+        assert_eq!(manager.update(&white()).unwrap(), RefreshOutcome::Unchanged);
+        manager.display_frame(&white()).unwrap();
+
+        assert_eq!(manager.inner().frames, [white()]);
+        assert!(manager.inner().partials.is_empty());
+    }
+
+    #[test]
+    fn changed_pixels_are_sent_as_a_byte_aligned_window() {
+        let mut manager = manager_with(RefreshPolicy::unlimited());
+        let mut frame = white();
+        frame[4 + 1] = 0b1110_1111; // x = 11, y = 1
+        frame[2 * 4 + 2] = 0b1111_1101; // x = 22, y = 2
+
+        let window = Rect::new(8, 1, 16, 2);
+        assert_eq!(manager.update(&frame).unwrap(), RefreshOutcome::Partial(window));
+        assert_eq!(
+            manager.inner().partials,
+            [(window, vec![0b1110_1111, 0xFF, 0xFF, 0b1111_1101])]
+        );
+        assert_eq!(manager.last_frame(), Some(frame.as_slice()));
+        assert_eq!(manager.partial_count(), 1);
+    }
+
+    #[test]
+    fn first_frame_is_sent_in_full() {
+        let mut manager = RefreshManager::new(RecordingDriver::default());
+        let mut frame = white();
+        frame[0] = 0x7F;
+        assert_eq!(manager.update(&frame).unwrap(), RefreshOutcome::Full);
+        assert_eq!(manager.inner().frames, [frame]);
+    }
+
+    // Frame with one black pixel in row `y`
+    fn dot(y: usize) -> Vec<u8> {
+        let mut frame = white();
+        frame[y * 4] = 0x7F;
+        frame
+    }
+
+    #[test]
+    fn partial_refresh_limit_promotes_the_next_refresh() {
+        let policy = RefreshPolicy::unlimited().max_partial_refreshes(Some(2));
+        let mut manager = manager_with(policy);
+        for y in 0..2 {
+            let outcome = manager.update(&dot(y)).unwrap();
+            assert!(matches!(outcome, RefreshOutcome::Partial(_)));
+        }
+        assert_eq!(manager.partial_count(), 2);
+
+        assert_eq!(manager.update(&dot(2)).unwrap(), RefreshOutcome::Full);
+        assert_eq!(manager.inner().frames, [white(), dot(2)]);
+        assert_eq!(manager.inner().partials.len(), 2);
+        assert_eq!(manager.partial_count(), 0);
+    }
+
+    #[test]
+    fn old_full_refresh_promotes_the_next_refresh() {
+        let policy = RefreshPolicy::unlimited().max_interval(Some(Duration::from_secs(60)));
+        let mut manager = manager_with(policy);
+        let outcome = manager.update(&dot(0)).unwrap();
+        assert!(matches!(outcome, RefreshOutcome::Partial(_)));
+
+        manager.last_full = Some(Instant::now() - Duration::from_secs(61));
+        assert_eq!(manager.update(&dot(1)).unwrap(), RefreshOutcome::Full);
+        assert_eq!(manager.inner().frames, [white(), dot(1)]);
+        assert!(manager.last_full_refresh().unwrap().elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn large_change_promotes_the_refresh() {
+        let policy = RefreshPolicy::unlimited().max_partial_area(Some(0.25));
+        let mut manager = manager_with(policy);
+        // One byte of one row is 1/32 of the panel
+        let outcome = manager.update(&dot(0)).unwrap();
+        assert_eq!(outcome, RefreshOutcome::Partial(Rect::new(0, 0, 8, 1)));
+
+        // Rows 0 to 3 are half of the panel
+        let mut frame = white();
+        frame[3 * 4 + 3] = 0xFE;
+        assert_eq!(manager.update(&frame).unwrap(), RefreshOutcome::Full);
+        assert_eq!(manager.inner().frames, [white(), frame]);
+        assert_eq!(manager.inner().partials.len(), 1);
+    }
+
+    #[test]
+    fn promoted_partial_sends_the_window_pasted_into_the_last_frame() {
+        let policy = RefreshPolicy::unlimited().max_partial_refreshes(Some(0));
+        let mut manager = manager_with(policy);
+        manager.display_partial(&Rect::new(8, 1, 8, 1), &[0x00]).unwrap();
+
+        let mut frame = white();
+        frame[4 + 1] = 0x00;
+        assert_eq!(manager.inner().frames, [white(), frame]);
+        assert!(manager.inner().partials.is_empty());
+    }
+
+    #[test]
+    fn partial_with_unknown_contents_requires_a_full_frame() {
+        let policy = RefreshPolicy::unlimited().max_partial_refreshes(Some(1));
+        let mut manager = RefreshManager::with_policy(RecordingDriver::default(), policy);
+        let rect = Rect::new(0, 0, 8, 1);
+        manager.display_partial(&rect, &[0x00]).unwrap();
+
+        let error = manager.display_partial(&rect, &[0xFF]);
+        assert!(matches!(error, Err(EpdError::FullRefreshRequired(_))));
+        assert_eq!(manager.inner().partials.len(), 1);
+        assert_eq!(manager.partial_count(), 1);
+
+        manager.display_frame(&white()).unwrap();
+        manager.display_partial(&rect, &[0x00]).unwrap();
+        assert_eq!(manager.inner().partials.len(), 2);
+    }
+
+    // Manager that has shown a white frame and promotes every partial refresh
+    fn promoting_manager() -> RefreshManager<SimulatedDisplay> {
+        let dir = std::env::temp_dir().join("epaper_ui_refresh_test");
+        let mut manager = RefreshManager::with_policy(
+            SimulatedDisplay::new(32, 8, dir),
+            RefreshPolicy::unlimited().max_partial_refreshes(Some(0)),
+        );
+        manager.last_frame = Some(vec![0xFF; manager.frame_size()]);
+        manager
+    }
+
+    #[test]
+    fn promoted_partial_rejects_an_unaligned_window() {
+        let mut manager = promoting_manager();
+        let error = manager.display_partial(&Rect::new(0, 0, 4, 2), &[0x00; 1]);
+        assert!(matches!(error, Err(EpdError::InvalidWindow { .. })));
+    }
+
+    #[test]
+    fn promoted_partial_rejects_a_window_outside_the_panel() {
+        let mut manager = promoting_manager();
+        let error = manager.display_partial(&Rect::new(24, 6, 16, 4), &[0x00; 8]);
+        assert!(matches!(error, Err(EpdError::InvalidWindow { .. })));
+    }
+
+    #[test]
+    fn promoted_partial_rejects_a_short_buffer() {
+        let mut manager = promoting_manager();
+        let error = manager.display_partial(&Rect::new(8, 0, 16, 4), &[0x00; 4]);
+        assert!(matches!(
+            error,
+            Err(EpdError::FrameSizeMismatch {
+                expected: 8,
+                actual: 4,
+            })
+        ));
+        assert_eq!(manager.inner().frame_count(), 0);
+    }
+}
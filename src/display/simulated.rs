@@ -0,0 +1,309 @@
+use crate::display::driver::{
+    check_buffer_size, frame_stride, paste_window, split_gray_planes, validate_window, window_size, DisplayDriver,
+    RefreshCapabilities,
+};
+use crate::display::error::{EpdError, Result};
+use crate::display::panel::Panel;
+use crate::ui::Rect;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Name of the refresh log written next to the frame images
+pub const REFRESH_LOG: &str = "refresh.log";
+
+/// A virtual panel that writes every refresh to a numbered PBM image
+///
+/// Frames are written as `frame_0001.pbm`, `frame_0002.pbm`, ... into the
+/// output directory (grayscale frames as `.pgm`, color frames as `.ppm`),
+/// and every operation is appended to `refresh.log` with its timestamp
+/// relative to the creation of the display. Partial refreshes are
+/// composited onto the previous frame before it is written.
+///
+/// A display created with `for_panel` only accepts the refresh modes of
+/// that panel, like the hardware driver would.
+pub struct SimulatedDisplay {
+    width: u32,
+    height: u32,
+    capabilities: RefreshCapabilities,
+    output_dir: PathBuf,
+    frame: Vec<u8>,
+    frame_count: u32,
+    started: Instant,
+    last_refresh: Option<Instant>,
+}
+
+impl SimulatedDisplay {
+    /// Create a virtual panel of any size that supports every refresh mode
+    pub fn new<P: AsRef<Path>>(width: u32, height: u32, output_dir: P) -> Self {
+        let capabilities = RefreshCapabilities {
+            partial: true,
+            fast: false,
+            grayscale: true,
+            color: true,
+        };
+        Self::with_capabilities(width, height, capabilities, output_dir)
+    }
+
+    /// Create a virtual panel with the size and refresh modes of `panel`
+    pub fn for_panel<P: AsRef<Path>>(panel: &Panel, output_dir: P) -> Self {
+        Self::with_capabilities(panel.width, panel.height, panel.capabilities(), output_dir)
+    }
+
+    fn with_capabilities<P: AsRef<Path>>(
+        width: u32,
+        height: u32,
+        capabilities: RefreshCapabilities,
+        output_dir: P,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            capabilities,
+            output_dir: output_dir.as_ref().to_path_buf(),
+            frame: vec![0xFF; frame_stride(width) * height as usize],
+            frame_count: 0,
+            started: Instant::now(),
+            last_refresh: None,
+        }
+    }
+
+    /// Directory the frames and log are written to
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    /// Number of frames written so far
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    /// Path of the image written for the given frame number (starting at 1)
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        self.output_dir.join(format!("frame_{:04}.pbm", frame))
+    }
+
+    /// Path of the image written for a grayscale frame
+    pub fn gray_frame_path(&self, frame: u32) -> PathBuf {
+        self.output_dir.join(format!("frame_{:04}.pgm", frame))
+    }
+
+    /// Path of the image written for a color frame
+    pub fn color_frame_path(&self, frame: u32) -> PathBuf {
+        self.output_dir.join(format!("frame_{:04}.ppm", frame))
+    }
+
+    /// The frame currently shown on the virtual panel
+    pub fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+
+    // Write the current frame as the next numbered image and log it
+    fn refresh(&mut self, kind: &str) -> Result<()> {
+        let start = Instant::now();
+        self.frame_count += 1;
+        let path = self.frame_path(self.frame_count);
+        self.write_pbm(&path, &self.frame)?;
+        self.log_refresh(kind, start, &path)
+    }
+
+    // Log a refresh that took `start.elapsed()` to write to `path`
+    fn log_refresh(&mut self, kind: &str, start: Instant, path: &Path) -> Result<()> {
+        let since_last = match self.last_refresh {
+            Some(last) => format!("{:.3}s", last.elapsed().as_secs_f64()),
+            None => "-".to_string(),
+        };
+        self.last_refresh = Some(Instant::now());
+
+        log::debug!("Simulated refresh written to {}", path.display());
+        self.log(&format!(
+            "frame {:04} {} write={:.3}ms since_last={} file={}",
+            self.frame_count,
+            kind,
+            start.elapsed().as_secs_f64() * 1000.0,
+            since_last,
+            path.display()
+        ))
+    }
+
+    // Write the frame buffer as a binary PBM (P4) image
+    fn write_pbm(&self, path: &Path, frame_buffer: &[u8]) -> Result<()> {
+        let mut file = File::create(path)?;
+        write!(file, "P4\n{} {}\n", self.width, self.height)?;
+
+        // PBM uses 1 for black, the frame buffer uses 1 for white
+        let inverted: Vec<u8> = frame_buffer.iter().map(|byte| !byte).collect();
+        file.write_all(&inverted)?;
+
+        Ok(())
+    }
+
+    // Write a 2 bits per pixel buffer as a binary PGM (P5) image
+    fn write_pgm(&self, path: &Path, gray_buffer: &[u8]) -> Result<()> {
+        let mut file = File::create(path)?;
+        write!(file, "P5\n{} {}\n3\n", self.width, self.height)?;
+
+        // Drop the padding pixels at the end of each row
+        let row_bytes = frame_stride(self.width) * 2;
+        let pixels: Vec<u8> = gray_buffer
+            .chunks(row_bytes)
+            .flat_map(|row| {
+                row.iter()
+                    .flat_map(|byte| (0..4).map(move |pixel| (byte >> (6 - 2 * pixel)) & 0x03))
+                    .take(self.width as usize)
+            })
+            .collect();
+        file.write_all(&pixels)?;
+
+        Ok(())
+    }
+
+    // Write a black plane and a red plane as a binary PPM (P6) image
+    fn write_ppm(&self, path: &Path, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        let mut file = File::create(path)?;
+        write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+
+        let stride = frame_stride(self.width);
+        let mut pixels = Vec::with_capacity(self.width as usize * self.height as usize * 3);
+        for y in 0..self.height as usize {
+            for x in 0..self.width as usize {
+                let addr = y * stride + x / 8;
+                let mask = 0x80 >> (x % 8);
+                // The color plane wins where both are set, as on the panels
+                let rgb = if chromatic[addr] & mask == 0 {
+                    [0xFF, 0x00, 0x00]
+                } else if black[addr] & mask == 0 {
+                    [0x00, 0x00, 0x00]
+                } else {
+                    [0xFF, 0xFF, 0xFF]
+                };
+                pixels.extend_from_slice(&rgb);
+            }
+        }
+        file.write_all(&pixels)?;
+
+        Ok(())
+    }
+
+    // Append a line to the refresh log
+    fn log(&self, message: &str) -> Result<()> {
+        let path = self.output_dir.join(REFRESH_LOG);
+        let mut log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        let elapsed = self.started.elapsed().as_secs_f64();
+        writeln!(log, "[{:>10.3}s] {}", elapsed, message)?;
+        Ok(())
+    }
+}
+
+impl DisplayDriver for SimulatedDisplay {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn capabilities(&self) -> RefreshCapabilities {
+        self.capabilities
+    }
+
+    fn init(&mut self) -> Result<()> {
+        fs::create_dir_all(&self.output_dir)?;
+        self.log(&format!("init {}x{}", self.width, self.height))
+    }
+
+    fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+        check_buffer_size(frame_buffer, self.frame_size())?;
+
+        self.frame.copy_from_slice(frame_buffer);
+        self.refresh("full")
+    }
+
+    fn display_partial(&mut self, rect: &Rect, buffer: &[u8]) -> Result<()> {
+        if !self.capabilities.partial {
+            return Err(EpdError::Unsupported("partial refresh"));
+        }
+        validate_window(rect, self.width, self.height)?;
+        check_buffer_size(buffer, window_size(rect))?;
+
+        paste_window(&mut self.frame, self.width, rect, buffer);
+        self.refresh(&format!(
+            "partial {},{} {}x{}",
+            rect.x, rect.y, rect.width, rect.height
+        ))
+    }
+
+    fn display_gray_frame(&mut self, gray_buffer: &[u8]) -> Result<()> {
+        if !self.capabilities.grayscale {
+            return Err(EpdError::Unsupported("grayscale"));
+        }
+        check_buffer_size(gray_buffer, self.frame_size() * 2)?;
+
+        let start = Instant::now();
+        self.frame_count += 1;
+        let path = self.gray_frame_path(self.frame_count);
+        self.write_pgm(&path, gray_buffer)?;
+
+        // Keep a thresholded copy for later partial refreshes
+        self.frame = split_gray_planes(gray_buffer).0;
+        self.log_refresh("gray4", start, &path)
+    }
+
+    fn display_color_frame(&mut self, black: &[u8], chromatic: &[u8]) -> Result<()> {
+        if !self.capabilities.color {
+            return Err(EpdError::Unsupported("color frames"));
+        }
+        check_buffer_size(black, self.frame_size())?;
+        check_buffer_size(chromatic, self.frame_size())?;
+
+        let start = Instant::now();
+        self.frame_count += 1;
+        let path = self.color_frame_path(self.frame_count);
+        self.write_ppm(&path, black, chromatic)?;
+
+        self.frame.copy_from_slice(black);
+        self.log_refresh("color", start, &path)
+    }
+
+    fn sleep(&mut self) -> Result<()> {
+        self.log("sleep")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::panel::{EPD_2IN13B_V4, EPD_7IN5_V2};
+
+    #[test]
+    fn panel_display_reports_and_enforces_the_panel_modes() {
+        let dir = std::env::temp_dir().join("epaper_ui_simulated_test");
+        let mut display = SimulatedDisplay::for_panel(&EPD_7IN5_V2, &dir);
+        assert_eq!(display.capabilities(), EPD_7IN5_V2.capabilities());
+        assert!(!display.capabilities().color);
+        assert_eq!((display.width(), display.height()), (800, 480));
+
+        let frame = vec![0xFF; display.frame_size()];
+        assert!(matches!(
+            display.display_color_frame(&frame, &frame),
+            Err(EpdError::Unsupported(_))
+        ));
+        assert!(matches!(
+            display.display_gray_frame(&[0xFF; 10]),
+            Err(EpdError::Unsupported(_))
+        ));
+        assert_eq!(display.frame_count(), 0);
+
+        let mut tricolor = SimulatedDisplay::for_panel(&EPD_2IN13B_V4, &dir);
+        assert!(tricolor.capabilities().color);
+        assert!(matches!(
+            tricolor.display_partial(&Rect::new(0, 0, 8, 8), &[0xFF; 8]),
+            Err(EpdError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::display::epd4in2::Epd4in2;
+use crate::display::error::{EpdError, Result};
+use crate::display::interface::EpdInterface;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// First bytes of every trace file
+pub const TRACE_MAGIC: &[u8; 8] = b"EPDTRACE";
+/// Version of the trace format written by `TraceWriter`
+pub const TRACE_VERSION: u8 = 1;
+
+// Record tags
+const TAG_COMMAND: u8 = 0x01;
+const TAG_DATA: u8 = 0x02;
+const TAG_READ: u8 = 0x03;
+const TAG_RESET: u8 = 0x04;
+const TAG_WAIT: u8 = 0x05;
+const TAG_DELAY: u8 = 0x06;
+
+/// One step of the conversation between the driver and the controller
+///
+/// The DC line is implied: low for commands, high for data and reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceRecord {
+    /// Command byte
+    Command(u8),
+    /// Data bytes following a command
+    Data(Vec<u8>),
+    /// Bytes read back from the controller
+    Read(Vec<u8>),
+    /// Level written to the reset line
+    Reset(bool),
+    /// Wait for the BUSY line and how long it took
+    Wait(Duration),
+    /// Plain delay in milliseconds
+    Delay(u64),
+}
+
+/// Writes a binary trace file
+///
+/// The file starts with `TRACE_MAGIC`, the format version and the panel
+/// name. Every record is a tag byte followed by a single byte (commands,
+/// reset levels), a little endian `u32` (waits and delays in milliseconds)
+/// or a `u32` length and the bytes (data and reads). Wait records are
+/// flushed to the file right away, so a killed process keeps the trace up to
+/// its last refresh.
+pub struct TraceWriter {
+    out: BufWriter<File>,
+}
+
+impl TraceWriter {
+    pub fn create<P: AsRef<Path>>(path: P, panel_name: &str) -> Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(TRACE_MAGIC)?;
+        out.write_all(&[TRACE_VERSION, panel_name.len() as u8])?;
+        out.write_all(panel_name.as_bytes())?;
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, record: &TraceRecord) -> Result<()> {
+        match record {
+            TraceRecord::Command(command) => self.out.write_all(&[TAG_COMMAND, *command])?,
+            TraceRecord::Data(bytes) => self.write_bytes(TAG_DATA, bytes)?,
+            TraceRecord::Read(bytes) => self.write_bytes(TAG_READ, bytes)?,
+            TraceRecord::Reset(high) => self.out.write_all(&[TAG_RESET, *high as u8])?,
+            TraceRecord::Wait(waited) => {
+                self.write_u32(TAG_WAIT, waited.as_millis().min(u32::MAX as u128) as u32)?;
+                self.out.flush()?
+            }
+            TraceRecord::Delay(ms) => self.write_u32(TAG_DELAY, (*ms).min(u32::MAX as u64) as u32)?,
+        }
+        Ok(())
+    }
+
+    /// Write buffered records to the file
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn write_u32(&mut self, tag: u8, value: u32) -> io::Result<()> {
+        self.out.write_all(&[tag])?;
+        self.out.write_all(&value.to_le_bytes())
+    }
+
+    fn write_bytes(&mut self, tag: u8, bytes: &[u8]) -> io::Result<()> {
+        self.write_u32(tag, bytes.len() as u32)?;
+        self.out.write_all(bytes)
+    }
+}
+
+/// Reads the records of a trace file written by `TraceWriter`
+pub struct TraceReader {
+    panel_name: String,
+    input: BufReader<File>,
+}
+
+impl TraceReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut input = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        let mut header = [0u8; 2];
+        input.read_exact(&mut header)?;
+        if &magic != TRACE_MAGIC || header[0] != TRACE_VERSION {
+            return Err(invalid_trace("not a version 1 trace file"));
+        }
+
+        let mut name = vec![0u8; header[1] as usize];
+        input.read_exact(&mut name)?;
+        let panel_name = String::from_utf8(name).map_err(|_| invalid_trace("bad panel name"))?;
+
+        Ok(Self { panel_name, input })
+    }
+
+    /// Name of the panel the trace was recorded on
+    pub fn panel_name(&self) -> &str {
+        &self.panel_name
+    }
+
+    // Read the next record, `None` at the end of the file
+    fn read_record(&mut self) -> Result<Option<TraceRecord>> {
+        let mut tag = [0u8; 1];
+        match self.input.read_exact(&mut tag) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error.into()),
+        }
+
+        let record = match tag[0] {
+            TAG_COMMAND => TraceRecord::Command(self.read_u8()?),
+            TAG_DATA => TraceRecord::Data(self.read_bytes()?),
+            TAG_READ => TraceRecord::Read(self.read_bytes()?),
+            TAG_RESET => TraceRecord::Reset(self.read_u8()? != 0),
+            TAG_WAIT => TraceRecord::Wait(Duration::from_millis(self.read_u32()?.into())),
+            TAG_DELAY => TraceRecord::Delay(self.read_u32()?.into()),
+            _ => return Err(invalid_trace("unknown record")),
+        };
+        Ok(Some(record))
+    }
+
+    fn read_u8(&mut self) -> io::Result<u8> {
+        let mut byte = [0u8; 1];
+        self.input.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        self.input.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_bytes(&mut self) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0u8; self.read_u32()? as usize];
+        self.input.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl Iterator for TraceReader {
+    type Item = Result<TraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+fn invalid_trace(reason: &str) -> EpdError {
+    EpdError::Io(io::Error::new(io::ErrorKind::InvalidData, reason.to_string()))
+}
+
+/// Send the records of a trace to a panel
+///
+/// Commands, data, reset levels and delays are repeated as recorded; waits
+/// poll the BUSY line of the panel like the original run did. Reads are
+/// skipped. Returns the number of records replayed.
+pub fn replay<I, T>(epd: &mut Epd4in2<I>, records: T) -> Result<u64>
+where
+    I: EpdInterface,
+    T: IntoIterator<Item = Result<TraceRecord>>,
+{
+    let mut count = 0;
+    for record in records {
+        match record? {
+            TraceRecord::Command(command) => epd.send_command(command)?,
+            TraceRecord::Data(bytes) => epd.send_data(&bytes)?,
+            TraceRecord::Read(_) => {}
+            TraceRecord::Reset(high) => epd.set_reset_line(high)?,
+            TraceRecord::Wait(_) => epd.wait_until_idle()?,
+            TraceRecord::Delay(ms) => epd.delay_ms(ms),
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_survive_a_write_read_round_trip() {
+        let path = std::env::temp_dir().join("epaper_ui_trace_test.trace");
+        let records = [
+            TraceRecord::Reset(false),
+            TraceRecord::Delay(10),
+            TraceRecord::Reset(true),
+            TraceRecord::Command(0x10),
+            TraceRecord::Data(vec![0xFF; 300]),
+            TraceRecord::Wait(Duration::from_millis(1500)),
+            TraceRecord::Read(vec![0x19, 0x80]),
+            TraceRecord::Data(Vec::new()),
+        ];
+
+        let mut writer = TraceWriter::create(&path, "epd4in2").unwrap();
+        for record in &records {
+            writer.record(record).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let reader = TraceReader::open(&path).unwrap();
+        assert_eq!(reader.panel_name(), "epd4in2");
+        let read: Vec<TraceRecord> = reader.map(|record| record.unwrap()).collect();
+        assert_eq!(read, records);
+    }
+
+    #[test]
+    fn wait_records_are_written_without_a_flush() {
+        let path = std::env::temp_dir().join("epaper_ui_trace_flush_test.trace");
+        let records = [
+            TraceRecord::Command(0x12),
+            TraceRecord::Wait(Duration::from_millis(20)),
+        ];
+
+        let mut writer = TraceWriter::create(&path, "epd4in2").unwrap();
+        for record in &records {
+            writer.record(record).unwrap();
+        }
+        let read: Vec<TraceRecord> = TraceReader::open(&path)
+            .unwrap()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(read, records);
+        drop(writer);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let path = std::env::temp_dir().join("epaper_ui_trace_test.bin");
+        std::fs::write(&path, b"P4\n8 1\n\x00\x00\x00\x00\x00").unwrap();
+        assert!(TraceReader::open(&path).is_err());
+    }
+}
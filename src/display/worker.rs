@@ -0,0 +1,343 @@
+use crate::display::driver::DisplayDriver;
+use crate::display::error::{EpdError, Result};
+use crate::ui::Rect;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Frame data handed to a `DisplayWorker`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameUpdate {
+    /// A full 1-bit frame, see `DisplayDriver::display_frame`
+    Full(Vec<u8>),
+    /// A byte aligned window, see `DisplayDriver::display_partial`
+    Partial { rect: Rect, buffer: Vec<u8> },
+    /// A 2-bit grayscale frame, see `DisplayDriver::display_gray_frame`
+    Gray(Vec<u8>),
+    /// A black and a red/yellow frame, see `DisplayDriver::display_color_frame`
+    Color { black: Vec<u8>, chromatic: Vec<u8> },
+}
+
+impl FrameUpdate {
+    /// Whether the update redraws the whole panel, replacing older updates
+    pub fn is_full(&self) -> bool {
+        !matches!(self, FrameUpdate::Partial { .. })
+    }
+
+    fn apply<D: DisplayDriver + ?Sized>(&self, driver: &mut D) -> Result<()> {
+        match self {
+            FrameUpdate::Full(frame) => driver.display_frame(frame),
+            FrameUpdate::Partial { rect, buffer } => driver.display_partial(rect, buffer),
+            FrameUpdate::Gray(frame) => driver.display_gray_frame(frame),
+            FrameUpdate::Color { black, chromatic } => driver.display_color_frame(black, chromatic),
+        }
+    }
+}
+
+/// Outcome of a request, reported by `DisplayWorker`
+#[derive(Debug)]
+pub enum WorkerEvent {
+    /// The request finished after the given time on the panel
+    Completed { id: u64, elapsed: Duration },
+    /// The request was dropped because a newer full frame was pending
+    Skipped { id: u64 },
+    /// The driver returned an error
+    Failed { id: u64, error: EpdError },
+}
+
+enum Request {
+    Update(u64, FrameUpdate),
+    Sleep(u64),
+    Shutdown,
+}
+
+/// Runs a display driver on its own thread
+///
+/// Frames are queued with `submit` and drawn in order. When several full
+/// frames are waiting while the panel refreshes, only the newest is drawn and
+/// the older ones are reported as `Skipped`; partial updates queued after the
+/// newest full frame are kept. Every request gets an id, and its outcome is
+/// reported through `try_event`/`events`.
+pub struct DisplayWorker<D: DisplayDriver + Send + 'static> {
+    requests: Sender<Request>,
+    events: Receiver<WorkerEvent>,
+    handle: Option<JoinHandle<D>>,
+    next_id: u64,
+}
+
+impl<D: DisplayDriver + Send + 'static> DisplayWorker<D> {
+    /// Move an initialized driver onto a new worker thread
+    pub fn spawn(driver: D) -> Result<Self> {
+        let (requests, request_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name("epd-worker".to_string())
+            .spawn(move || run(driver, request_rx, event_tx))?;
+
+        Ok(Self {
+            requests,
+            events,
+            handle: Some(handle),
+            next_id: 1,
+        })
+    }
+
+    /// Queue a frame and return the id its event will carry
+    pub fn submit(&mut self, update: FrameUpdate) -> Result<u64> {
+        let id = self.next_request_id();
+        self.send(Request::Update(id, update))?;
+        Ok(id)
+    }
+
+    /// Queue a full frame
+    pub fn display_frame(&mut self, frame: Vec<u8>) -> Result<u64> {
+        self.submit(FrameUpdate::Full(frame))
+    }
+
+    /// Queue a partial refresh of a window
+    pub fn display_partial(&mut self, rect: Rect, buffer: Vec<u8>) -> Result<u64> {
+        self.submit(FrameUpdate::Partial { rect, buffer })
+    }
+
+    /// Queue putting the panel to sleep once the pending frames are drawn
+    pub fn sleep(&mut self) -> Result<u64> {
+        let id = self.next_request_id();
+        self.send(Request::Sleep(id))?;
+        Ok(id)
+    }
+
+    /// Next event, if one is ready
+    pub fn try_event(&self) -> Option<WorkerEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Receiver of all events, e.g. for a blocking `recv_timeout`
+    pub fn events(&self) -> &Receiver<WorkerEvent> {
+        &self.events
+    }
+
+    /// Draw the pending frames, stop the thread and return the driver
+    pub fn shutdown(mut self) -> Result<D> {
+        let handle = self.handle.take().ok_or(EpdError::WorkerStopped)?;
+        let _ = self.requests.send(Request::Shutdown);
+        handle.join().map_err(|_| EpdError::WorkerStopped)
+    }
+
+    fn next_request_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn send(&self, request: Request) -> Result<()> {
+        self.requests
+            .send(request)
+            .map_err(|_| EpdError::WorkerStopped)
+    }
+}
+
+impl<D: DisplayDriver + Send + 'static> Drop for DisplayWorker<D> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = self.requests.send(Request::Shutdown);
+            let _ = handle.join();
+        }
+    }
+}
+
+// Worker thread: wait for requests, coalesce the queue and drive the panel
+fn run<D: DisplayDriver>(mut driver: D, requests: Receiver<Request>, events: Sender<WorkerEvent>) -> D {
+    while let Ok(first) = requests.recv() {
+        let mut batch = vec![first];
+        batch.extend(requests.try_iter());
+
+        let shutdown = batch.iter().position(|request| matches!(request, Request::Shutdown));
+        if let Some(index) = shutdown {
+            batch.truncate(index);
+        }
+
+        // Everything queued before the newest full frame is stale
+        let newest_full = batch
+            .iter()
+            .rposition(|request| matches!(request, Request::Update(_, update) if update.is_full()))
+            .unwrap_or(0);
+
+        for (index, request) in batch.into_iter().enumerate() {
+            let started = Instant::now();
+            let event = match request {
+                Request::Update(id, _) | Request::Sleep(id) if index < newest_full => {
+                    log::debug!("Skipping stale display request {}", id);
+                    WorkerEvent::Skipped { id }
+                }
+                Request::Update(id, update) => finish(id, started, update.apply(&mut driver)),
+                Request::Sleep(id) => finish(id, started, driver.sleep()),
+                Request::Shutdown => continue,
+            };
+            // The owner may have stopped listening; keep drawing anyway
+            let _ = events.send(event);
+        }
+
+        if shutdown.is_some() {
+            break;
+        }
+    }
+    driver
+}
+
+fn finish(id: u64, started: Instant, result: Result<()>) -> WorkerEvent {
+    match result {
+        Ok(()) => WorkerEvent::Completed {
+            id,
+            elapsed: started.elapsed(),
+        },
+        Err(error) => {
+            log::error!("Display request {} failed: {}", id, error);
+            WorkerEvent::Failed { id, error }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::simulated::SimulatedDisplay;
+    use std::sync::mpsc::SyncSender;
+
+    // Driver whose first frame blocks until the test releases it, so the
+    // following requests queue up as they would during a slow refresh
+    struct SlowDriver {
+        started: SyncSender<()>,
+        release: Receiver<()>,
+        frames: Vec<Vec<u8>>,
+        partials: Vec<Rect>,
+        sleeps: u32,
+    }
+
+    impl SlowDriver {
+        fn new() -> (Self, Receiver<()>, SyncSender<()>) {
+            let (started, started_rx) = mpsc::sync_channel(1);
+            let (release_tx, release) = mpsc::sync_channel(1);
+            let driver = Self {
+                started,
+                release,
+                frames: Vec::new(),
+                partials: Vec::new(),
+                sleeps: 0,
+            };
+            (driver, started_rx, release_tx)
+        }
+    }
+
+    impl DisplayDriver for SlowDriver {
+        fn width(&self) -> u32 {
+            8
+        }
+
+        fn height(&self) -> u32 {
+            1
+        }
+
+        fn init(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn display_frame(&mut self, frame_buffer: &[u8]) -> Result<()> {
+            if self.frames.is_empty() {
+                let _ = self.started.send(());
+                let _ = self.release.recv();
+            }
+            if frame_buffer.is_empty() {
+                return Err(EpdError::Unsupported("empty frames"));
+            }
+            self.frames.push(frame_buffer.to_vec());
+            Ok(())
+        }
+
+        fn display_partial(&mut self, rect: &Rect, _buffer: &[u8]) -> Result<()> {
+            self.partials.push(*rect);
+            Ok(())
+        }
+
+        fn sleep(&mut self) -> Result<()> {
+            self.sleeps += 1;
+            Ok(())
+        }
+    }
+
+    // Outcome of every request, as (id, "completed" | "skipped" | "failed")
+    fn outcomes(worker: &DisplayWorker<SlowDriver>, count: usize) -> Vec<(u64, &'static str)> {
+        (0..count)
+            .map(|_| match worker.events().recv_timeout(Duration::from_secs(5)).unwrap() {
+                WorkerEvent::Completed { id, .. } => (id, "completed"),
+                WorkerEvent::Skipped { id } => (id, "skipped"),
+                WorkerEvent::Failed { id, .. } => (id, "failed"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_the_newest_full_frame_is_drawn() {
+        let (driver, started, release) = SlowDriver::new();
+        let mut worker = DisplayWorker::spawn(driver).unwrap();
+
+        worker.display_frame(vec![0x01]).unwrap();
+        started.recv().unwrap();
+        worker.display_frame(vec![0x02]).unwrap();
+        worker.sleep().unwrap();
+        worker.display_frame(vec![0x03]).unwrap();
+        worker.display_partial(Rect::new(0, 0, 8, 1), vec![0x04]).unwrap();
+        release.send(()).unwrap();
+
+        assert_eq!(
+            outcomes(&worker, 5),
+            [(1, "completed"), (2, "skipped"), (3, "skipped"), (4, "completed"), (5, "completed")]
+        );
+        let driver = worker.shutdown().unwrap();
+        assert_eq!(driver.frames, [vec![0x01], vec![0x03]]);
+        assert_eq!(driver.partials, [Rect::new(0, 0, 8, 1)]);
+        assert_eq!(driver.sleeps, 0);
+    }
+
+    #[test]
+    fn requests_after_the_newest_full_frame_are_kept() {
+        let (driver, started, release) = SlowDriver::new();
+        let mut worker = DisplayWorker::spawn(driver).unwrap();
+
+        worker.display_frame(vec![0x01]).unwrap();
+        started.recv().unwrap();
+        worker.display_frame(Vec::new()).unwrap();
+        worker.sleep().unwrap();
+        release.send(()).unwrap();
+
+        assert_eq!(outcomes(&worker, 3), [(1, "completed"), (2, "failed"), (3, "completed")]);
+        assert_eq!(worker.shutdown().unwrap().sleeps, 1);
+    }
+
+    #[test]
+    fn shutdown_draws_the_pending_frames() {
+        let (driver, started, release) = SlowDriver::new();
+        let mut worker = DisplayWorker::spawn(driver).unwrap();
+
+        worker.display_frame(vec![0x01]).unwrap();
+        started.recv().unwrap();
+        worker.display_frame(vec![0x02]).unwrap();
+        release.send(()).unwrap();
+
+        assert_eq!(worker.shutdown().unwrap().frames, [vec![0x01], vec![0x02]]);
+    }
+
+    #[test]
+    fn boxed_displays_can_be_moved_to_a_worker() {
+        let dir = std::env::temp_dir().join("epaper_ui_worker_test");
+        let mut display: Box<dyn DisplayDriver + Send> = Box::new(SimulatedDisplay::new(8, 1, dir));
+        display.init().unwrap();
+
+        let mut worker = DisplayWorker::spawn(display).unwrap();
+        let id = worker.display_frame(vec![0x00]).unwrap();
+        match worker.events().recv_timeout(Duration::from_secs(5)).unwrap() {
+            WorkerEvent::Completed { id: done, .. } => assert_eq!(done, id),
+            event => panic!("unexpected event {:?}", event),
+        }
+        worker.shutdown().unwrap();
+    }
+}
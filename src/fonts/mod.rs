@@ -13,7 +13,7 @@ impl Font {
     pub fn has_char(&self, c: char) -> bool {
         let char_code = c as u32;
         // Assuming ASCII only for simplicity
-        char_code >= 32 && char_code <= 126
+        (32..=126).contains(&char_code)
     }
     
     pub fn get_char_offset(&self, c: char) -> usize {
@@ -114,7 +114,10 @@ impl Component for Button {
         let text_x = self.bounds.x + (self.bounds.width - text_width) / 2;
         let text_y = self.bounds.y + (self.bounds.height - char_height) / 2;
 
-        paint.draw_string_at(text_x, text_y, &self.label, self.font, text_color);
+        // Keep long captions inside the border
+        paint.with_clip(self.bounds, |paint| {
+            paint.draw_string_at(text_x, text_y, &self.label, self.font, text_color);
+        });
 
         Ok(())
     }
@@ -3,7 +3,7 @@ use anyhow::Result;
 use downcast_rs::{Downcast, impl_downcast};
 
 /// Defines the position and size of a UI component
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Rect {
     pub x: i32,
     pub y: i32,
@@ -19,6 +19,15 @@ impl Rect {
     pub fn contains(&self, x: i32, y: i32) -> bool {
         x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
     }
+
+    /// Area covered by both rectangles, zero sized when they do not overlap
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        Rect::new(x, y, (right - x).max(0), (bottom - y).max(0))
+    }
 }
 
 /// Base trait for all UI components
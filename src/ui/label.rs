@@ -1,4 +1,4 @@
-use crate::display::{Paint, COLORED};
+use crate::display::{ColorPlane, Paint, COLORED};
 use crate::fonts::Font;
 use crate::ui::component::{Component, Rect};
 use anyhow::Result;
@@ -14,6 +14,7 @@ pub struct Label {
     text: String,
     font: &'static Font,
     alignment: TextAlignment,
+    highlighted: bool,
 }
 
 impl Label {
@@ -23,6 +24,7 @@ impl Label {
             text: text.to_string(),
             font,
             alignment: TextAlignment::Left,
+            highlighted: false,
         }
     }
 
@@ -41,6 +43,15 @@ impl Label {
     pub fn alignment(&self) -> &TextAlignment {
         &self.alignment
     }
+
+    /// Draw the text in red/yellow on tri-color panels (black otherwise)
+    pub fn set_highlighted(&mut self, highlighted: bool) {
+        self.highlighted = highlighted;
+    }
+
+    pub fn is_highlighted(&self) -> bool {
+        self.highlighted
+    }
 }
 
 impl Component for Label {
@@ -72,7 +83,15 @@ impl Component for Label {
         // Vertically center the text
         let text_y = self.bounds.y + (self.bounds.height - self.font.height as i32) / 2;
         
-        paint.draw_string_at(text_x, text_y, &self.text, self.font, COLORED);
+        let plane = paint.get_plane();
+        if self.highlighted {
+            paint.set_plane(ColorPlane::Chromatic);
+        }
+        // Cut off text that does not fit the label
+        paint.with_clip(self.bounds, |paint| {
+            paint.draw_string_at(text_x, text_y, &self.text, self.font, COLORED);
+        });
+        paint.set_plane(plane);
         
         Ok(())
     }
@@ -83,4 +102,36 @@ impl Component for Label {
         // Nothing to update for a basic label
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fonts::FONT12;
+
+    fn has_ink(paint: &Paint, x: i32, y: i32) -> bool {
+        let stride = paint.get_width().div_ceil(8) as usize;
+        paint.get_image()[y as usize * stride + x as usize / 8] & (0x80 >> (x % 8)) == 0
+    }
+
+    #[test]
+    fn text_is_cut_at_the_bounds() {
+        // FONT12 only has glyphs for ' ' and '!'; each '!' is a bar in column 3
+        let mut unclipped = Paint::new(48, 20);
+        unclipped.draw_string_at(2, 4, "!!!!", &FONT12, COLORED);
+        assert!((12..48).any(|x| (0..20).any(|y| has_ink(&unclipped, x, y))));
+
+        let mut paint = Paint::new(48, 20);
+        let label = Label::new(2, 2, 10, 16, "!!!!", &FONT12);
+        label.render(&mut paint).unwrap();
+        for y in 0..20 {
+            for x in 0..48 {
+                if has_ink(&paint, x, y) {
+                    assert!(label.bounds().contains(x, y), "ink at ({}, {})", x, y);
+                }
+            }
+        }
+        assert!((2..12).any(|x| (2..18).any(|y| has_ink(&paint, x, y))));
+        assert_eq!(paint.get_clip(), None);
+    }
+}
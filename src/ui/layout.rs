@@ -1,6 +1,5 @@
 use crate::ui::component::{Component, Rect};
 use anyhow::Result;
-use std::collections::HashMap;
 
 pub enum Orientation {
     Horizontal,
@@ -78,7 +77,7 @@ impl Layout {
                 
                 // Position and size each component
                 for child in &mut self.children {
-                    let mut child_bounds = child.bounds().clone();
+                    let mut child_bounds = *child.bounds();
                     
                     // Set width if it's a flex component
                     if child_bounds.width <= 0 {
@@ -122,7 +121,7 @@ impl Layout {
                 
                 // Position and size each component
                 for child in &mut self.children {
-                    let mut child_bounds = child.bounds().clone();
+                    let mut child_bounds = *child.bounds();
                     
                     // Set height if it's a flex component
                     if child_bounds.height <= 0 {
@@ -170,12 +169,13 @@ impl Component for Layout {
     }
 
     fn render(&self, paint: &mut crate::display::Paint) -> Result<()> {
-        // Render all children
-        for child in &self.children {
-            child.render(paint)?;
-        }
-        
-        Ok(())
+        // Render all children, clipped to the layout
+        paint.with_clip(self.bounds, |paint| {
+            for child in &self.children {
+                child.render(paint)?;
+            }
+            Ok(())
+        })
     }
 
     fn handle_tap(&mut self, x: i32, y: i32) -> bool {
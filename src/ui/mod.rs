@@ -2,8 +2,10 @@ pub mod component;
 pub mod button;
 pub mod label;
 pub mod layout;
+pub mod screen;
 
 pub use component::{Component, Rect};
 pub use button::Button;
 pub use label::{Label, TextAlignment};
-pub use layout::{Layout, Orientation};
\ No newline at end of file
+pub use layout::{Layout, Orientation};
+pub use screen::Screen;
\ No newline at end of file
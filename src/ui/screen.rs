@@ -0,0 +1,82 @@
+use crate::display::{DisplayDriver, Paint, UNCOLORED};
+use crate::ui::component::Component;
+use anyhow::Result;
+
+/// A panel with its own frame buffer and component tree
+///
+/// Use one `Screen` per panel when several panels are connected, e.g. the
+/// two halves of a split dashboard.
+pub struct Screen<D: DisplayDriver> {
+    driver: D,
+    paint: Paint,
+    root: Box<dyn Component>,
+}
+
+impl<D: DisplayDriver> Screen<D> {
+    /// Create a screen; `root` is resized to cover the whole panel
+    pub fn new(driver: D, mut root: Box<dyn Component>) -> Self {
+        let width = driver.width();
+        let height = driver.height();
+        let paint = if driver.capabilities().color {
+            Paint::new_tricolor(width, height)
+        } else {
+            Paint::new(width, height)
+        };
+
+        root.set_position(0, 0);
+        root.set_size(width as i32, height as i32);
+
+        Self {
+            driver,
+            paint,
+            root,
+        }
+    }
+
+    pub fn driver(&self) -> &D {
+        &self.driver
+    }
+
+    pub fn driver_mut(&mut self) -> &mut D {
+        &mut self.driver
+    }
+
+    /// Release the driver
+    pub fn into_driver(self) -> D {
+        self.driver
+    }
+
+    pub fn paint(&self) -> &Paint {
+        &self.paint
+    }
+
+    /// Frame buffer of this screen, e.g. to change the rotation
+    pub fn paint_mut(&mut self) -> &mut Paint {
+        &mut self.paint
+    }
+
+    pub fn root(&self) -> &dyn Component {
+        self.root.as_ref()
+    }
+
+    /// Root of the component tree, use `downcast_mut` to reach its type
+    pub fn root_mut(&mut self) -> &mut dyn Component {
+        self.root.as_mut()
+    }
+
+    /// Clear the frame buffer and render the component tree into it
+    pub fn render(&mut self) -> Result<()> {
+        self.paint.clear(UNCOLORED);
+        self.root.render(&mut self.paint)
+    }
+
+    /// Render the component tree and send it to the panel
+    pub fn refresh(&mut self) -> Result<()> {
+        self.render()?;
+        match self.paint.get_color_image() {
+            Some(color) => self.driver.display_color_frame(self.paint.get_image(), color)?,
+            None => self.driver.display_frame(self.paint.get_image())?,
+        }
+        Ok(())
+    }
+}